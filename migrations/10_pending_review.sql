@@ -0,0 +1,14 @@
+-- Set for songs added by imports nobody confirmed the metadata of, they're left out of the library until approved
+ALTER TABLE songs ADD COLUMN pending_review BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Metadata a song waiting for review was imported with, artists are a JSON array
+CREATE TABLE pending_reviews (
+	song_id INTEGER PRIMARY KEY NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+	title TEXT NOT NULL,
+	album TEXT,
+	artists TEXT NOT NULL,
+	album_artist TEXT,
+	track INTEGER,
+	disc INTEGER,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,8 @@
+-- Sources a library scan added where they already were, their objects aren't ours to move or delete
+ALTER TABLE sources ADD COLUMN in_place BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE sources SET in_place = TRUE WHERE id IN (
+    SELECT sts.source_id FROM songs_to_sources sts
+    JOIN song_provenance p ON p.song_id = sts.song_id
+    WHERE p.method = 'scan'
+);
@@ -0,0 +1,20 @@
+CREATE TABLE source_verifications (
+	source_id INTEGER PRIMARY KEY NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+	ok BOOLEAN NOT NULL,
+	duration REAL NOT NULL,
+	declared_duration REAL,
+	packets INTEGER NOT NULL,
+	lost_packets INTEGER NOT NULL,
+	error TEXT,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER update_source_verifications
+AFTER UPDATE ON source_verifications
+FOR EACH ROW
+BEGIN
+    UPDATE source_verifications
+    SET updated_at = CURRENT_TIMESTAMP
+    WHERE source_id = OLD.source_id;
+END;
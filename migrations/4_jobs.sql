@@ -0,0 +1,43 @@
+CREATE TABLE jobs (
+	id INTEGER PRIMARY KEY NOT NULL,
+	kind TEXT NOT NULL,
+	params TEXT NOT NULL,
+	status TEXT NOT NULL DEFAULT 'queued',
+	progress INTEGER NOT NULL DEFAULT 0,
+	total INTEGER,
+	error TEXT,
+	created_by TEXT REFERENCES users(username) ON DELETE SET NULL,
+	started_at DATETIME,
+	finished_at DATETIME,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE job_logs (
+	id INTEGER PRIMARY KEY NOT NULL,
+	job_id INTEGER NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+	level TEXT NOT NULL,
+	message TEXT NOT NULL,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE job_results (
+	id INTEGER PRIMARY KEY NOT NULL,
+	job_id INTEGER NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+	item TEXT NOT NULL,
+	ok BOOLEAN NOT NULL,
+	detail TEXT,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX job_logs_job_id ON job_logs(job_id);
+CREATE INDEX job_results_job_id ON job_results(job_id);
+
+CREATE TRIGGER update_jobs
+AFTER UPDATE ON jobs
+FOR EACH ROW
+BEGIN
+    UPDATE jobs
+    SET updated_at = CURRENT_TIMESTAMP
+    WHERE id = OLD.id;
+END;
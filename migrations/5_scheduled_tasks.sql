@@ -0,0 +1,20 @@
+CREATE TABLE scheduled_tasks (
+	name TEXT PRIMARY KEY NOT NULL,
+	every_secs INTEGER NOT NULL,
+	last_job_id INTEGER REFERENCES jobs(id) ON DELETE SET NULL,
+	last_run_at DATETIME,
+	last_status TEXT,
+	last_error TEXT,
+	next_run_at DATETIME,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER update_scheduled_tasks
+AFTER UPDATE ON scheduled_tasks
+FOR EACH ROW
+BEGIN
+    UPDATE scheduled_tasks
+    SET updated_at = CURRENT_TIMESTAMP
+    WHERE name = OLD.name;
+END;
@@ -0,0 +1,13 @@
+ALTER TABLE songs ADD COLUMN metadata_edited BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE metadata_diffs (
+	song_id INTEGER PRIMARY KEY NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+	job_id INTEGER REFERENCES jobs(id) ON DELETE SET NULL,
+	current_title TEXT NOT NULL,
+	current_album TEXT,
+	current_artists TEXT NOT NULL,
+	title TEXT NOT NULL,
+	album TEXT,
+	artists TEXT NOT NULL,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
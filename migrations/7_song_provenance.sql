@@ -0,0 +1,18 @@
+CREATE TABLE song_provenance (
+	song_id INTEGER PRIMARY KEY NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+	uploaded_by TEXT REFERENCES users(username) ON DELETE SET NULL,
+	-- ws, form, presigned, yt, url, scan or watch
+	method TEXT NOT NULL,
+	-- Original filename or the url it was downloaded from
+	original_name TEXT,
+	video_id TEXT,
+	channel TEXT,
+	channel_url TEXT,
+	upload_date TEXT,
+	webpage_url TEXT,
+	-- Playlist or channel the song was imported from
+	playlist_url TEXT,
+	imported_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX song_provenance_video_id ON song_provenance(video_id);
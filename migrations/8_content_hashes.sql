@@ -0,0 +1,4 @@
+-- SHA-256 of the source's data as lowercase hex, null until it's hashed
+ALTER TABLE sources ADD COLUMN content_hash TEXT;
+
+CREATE INDEX sources_content_hash ON sources(content_hash);
@@ -0,0 +1,8 @@
+CREATE TABLE song_fingerprints (
+	song_id INTEGER PRIMARY KEY NOT NULL REFERENCES songs(id) ON DELETE CASCADE,
+	-- Sub-fingerprints as little endian u32s
+	fingerprint BLOB NOT NULL,
+	-- Seconds of audio in the whole song, not just the fingerprinted part
+	duration REAL NOT NULL,
+	created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -1,23 +1,47 @@
-use std::sync::Arc;
+use std::{fmt, path::Path, sync::Arc};
 
 use axum::{
-    body::Bytes,
+    Json,
     extract::{self, ws::WebSocket},
     response::Response,
 };
 use axum_extra::extract::CookieJar;
+use opendal::Operator;
 use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use symphonia::core::io::MediaSource;
+use tokio::io::AsyncWriteExt;
 
 use crate::{
     ApiError,
-    api::audio::{InitSongInfo, YtInitSongInfo, get_metadata},
-    db::{self, Album, Artist, Song, StorageBackend, User},
+    api::audio::{
+        DecodeReport, InitSongInfo, ParsedMetadata, UploadedInitSongInfo, YtInitSongInfo,
+        get_metadata, verify_audio,
+    },
+    content_hash,
+    db::{
+        self, Album, Artist, Song, Source, StorageBackend, Tag, User,
+        provenance::{ImportMethod, SongProvenance},
+        review::PendingReview,
+    },
+    importers::{Imported, MetadataHints, PendingImport},
+    storage_path::{PathFields, extension_for_mime_type, unused_path},
+    yt_dlp::{is_collection_url, video_id_from_url},
 };
 
 use super::{
     State,
+    archive::{ExtractedAlbum, is_archive},
     audio::AlbumCover,
     auth::{self, AUTH_COOKIE},
+    filename_template::FilenameTemplate,
+    protocol::{
+        self, ArchiveInfo, BatchInfo, ClientMessage, Decision, DuplicateInfo, ErrorInfo,
+        FailedItem, ItemFailure, ItemStage, ServerMessage, SkippedItem, SongMetadata,
+        SucceededItem, SummaryInfo, TrackMetadata,
+    },
+    replace_audio::replace_audio,
+    upload::{MAX_CHUNK_SIZE, StagedFile, move_object},
 };
 
 pub async fn handler(
@@ -33,8 +57,9 @@ pub async fn handler(
     Ok(ws
         .max_write_buffer_size(128 * 1024)
         .write_buffer_size(16 * 1024)
-        .max_message_size(128 * 1024 * 1024)
-        .max_frame_size(128 * 1024 * 1024)
+        // Room for the offset header on top of a full chunk
+        .max_message_size(MAX_CHUNK_SIZE + 1024)
+        .max_frame_size(MAX_CHUNK_SIZE + 1024)
         .on_upgrade(move |ws| async move {
             let state = state;
             let user = user;
@@ -46,7 +71,7 @@ pub async fn handler(
         }))
 }
 
-const ALLOWED_MIME_TYPES: [&str; 6] = [
+pub const ALLOWED_MIME_TYPES: [&str; 6] = [
     "audio/flac",
     "audio/mp3",
     "audio/mpeg",
@@ -55,308 +80,1192 @@ const ALLOWED_MIME_TYPES: [&str; 6] = [
     "audio/x-m4a",
 ];
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/FinalMetadata.ts")]
 #[serde(rename_all = "camelCase")]
-struct ClientMetadata {
-    title: Option<Arc<str>>,
-    album: Option<Arc<str>>,
-    artists: Vec<Arc<str>>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct FinalMetadata {
+pub struct FinalMetadata {
     title: Arc<str>,
     album: Option<Arc<str>>,
     artists: Arc<[Box<str>]>,
     #[serde(default = "default_storage_backend_name")]
+    #[ts(as = "Option<String>", optional)]
     storage_backend: Arc<str>,
+    /// Only used for the song's path, taken from the file if left out
+    #[serde(default)]
+    #[ts(optional)]
+    album_artist: Option<Arc<str>>,
+    #[serde(default)]
+    #[ts(optional)]
+    track: Option<u32>,
+    #[serde(default)]
+    #[ts(optional)]
+    disc: Option<u32>,
+}
+
+impl FinalMetadata {
+    /// Fills in what's only used for the path from the parsed metadata
+    fn or_parsed(self, parsed_meta: &ParsedMetadata) -> Self {
+        Self {
+            album_artist: self
+                .album_artist
+                .or_else(|| parsed_meta.album_artist.clone()),
+            track: self.track.or(parsed_meta.track),
+            disc: self.disc.or(parsed_meta.disc),
+            ..self
+        }
+    }
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/AddSongResult.ts")]
 #[serde(rename_all = "camelCase")]
-struct AddSongResult {
+pub struct AddSongResult {
+    #[ts(type = "number")]
+    pub song_id: i64,
     created_album: Option<bool>,
     added_album: Option<bool>,
     created_artists: Option<bool>,
     added_artists: Option<bool>,
 }
 
-#[derive(Debug, Serialize)]
-struct Error {
-    error: String,
+/// Where the audio of a song being added is
+#[derive(Debug, Clone, Copy)]
+pub enum SongFile<'a> {
+    /// Staged on our disk, gets streamed into the storage backend
+    Staged(&'a StagedFile),
+    /// Already uploaded to a staging key in the storage backend, gets moved to its final path
+    InBackend { key: &'a str, mime_type: &'a str },
+    /// Already in the storage backend where it should stay, like a library that was scanned
+    InPlace { path: &'a str, mime_type: &'a str },
+    /// Another song's source with the same content, which the new song is linked to
+    Existing(&'a Source),
+}
+
+/// The source a song is inserted with
+enum NewSongSource<'a> {
+    New {
+        path: &'a str,
+        mime_type: &'a str,
+        content_hash: Option<&'a str>,
+        in_place: bool,
+    },
+    Existing(i64),
+}
+
+/// Why adding a song failed. Nothing about it is left in the db, and whatever was
+/// written to the storage backend is removed again.
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/AddSongFailure.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct AddSongFailure {
+    pub step: AddSongStep,
+    pub error: String,
+    /// Objects in the storage backend that couldn't be removed
+    pub left_behind: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/AddSongStep.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum AddSongStep {
+    WriteSong,
+    WriteCover,
+    InsertSong,
+    RecordProvenance,
+    QueueReview,
+    AddAlbum,
+    AddArtists,
+    Commit,
 }
 
-/// 1. Client sends metadata on songs they want to upload
-/// 2. We verify the metadata
-/// 3. For each song to be uploaded
-///     1. The client sends the file
+impl fmt::Display for AddSongFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let step = match self.step {
+            AddSongStep::WriteSong => "writing the file",
+            AddSongStep::WriteCover => "writing the album cover",
+            AddSongStep::InsertSong => "adding the song",
+            AddSongStep::RecordProvenance => "recording where it came from",
+            AddSongStep::QueueReview => "queueing it for review",
+            AddSongStep::AddAlbum => "adding its album",
+            AddSongStep::AddArtists => "adding its artists",
+            AddSongStep::Commit => "saving it",
+        };
+        write!(f, "Couldn't add song while {step}: {}", self.error)?;
+        if !self.left_behind.is_empty() {
+            write!(f, " (left behind {})", self.left_behind.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// What happened to one item of the batch
+enum ItemOutcome {
+    Added(Vec<i64>),
+    Skipped,
+    Failed {
+        stage: ItemStage,
+        error: String,
+        retryable: bool,
+    },
+}
+
+impl ItemOutcome {
+    fn failed(stage: ItemStage, err: ApiError, retryable: bool) -> Self {
+        Self::Failed {
+            stage,
+            error: format!("{err}"),
+            retryable,
+        }
+    }
+}
+
+/// 1. Client says hello with the protocol version it speaks (see [`protocol`])
+/// 2. Client sends metadata on songs they want to upload
+/// 3. We verify the metadata, expand playlists and tell the client how many songs to expect
+/// 4. For each song to be uploaded
+///     1. The client sends the file in chunks (see [`ServerMessage::Upload`]), or we send
+///        progress while yt-dlp downloads it
 ///     2. We parse metadata in the file and send back to client
-///     3. Client sends back final metadata for file
+///     3. Client sends back final metadata for file, a song whose audio it replaces, or skips it
 ///     4. We save the file in a storage backend and in the database
-/// 4. Done
-async fn handle_ws(mut ws: WebSocket, state: State, _user: User) -> Result<(), ApiError> {
-    let info_str = ws
-        .recv()
-        .await
-        .ok_or(ApiError::InvalidWSMessage)??
-        .into_text()?;
-    let info: Box<[Arc<InitSongInfo>]> = serde_json::from_str(&info_str)?;
+///
+///     If the item fails, we send [`ServerMessage::ItemFailed`] and the client skips or retries it
+/// 5. We send a [`ServerMessage::Summary`] of the whole batch
+async fn handle_ws(mut ws: WebSocket, state: State, user: User) -> Result<(), ApiError> {
+    if !protocol::handshake(&mut ws).await? {
+        return Ok(());
+    }
+
+    let info = match ClientMessage::recv(&mut ws).await? {
+        ClientMessage::Songs(info) => info,
+        _ => return close_with_error(ws, "Expected the songs to import".into()).await,
+    };
 
     tracing::debug!("Uploading: {info:#?}");
 
     for info in &info {
         if let Some(mime_type) = info.mime_type()
             && !ALLOWED_MIME_TYPES.contains(&mime_type)
+            && !is_archive(mime_type)
         {
             return close_with_error(ws, format!("Invalid mime type: {}", mime_type)).await;
         }
     }
 
-    for song in info {
-        // Client sends song data up or we get it from yt-dlp
-        let (song_data, mime_type) = match &*song {
-            InitSongInfo::Yt(yt_init_song_info) => yt_dlp_song(&yt_init_song_info, &*state.config.yt_dlp_cookies_path).await?,
-            InitSongInfo::Uploaded(uploaded_init_song_info) => (
-                ws.recv()
-                    .await
-                    .ok_or(ApiError::InvalidWSMessage)??
-                    .into_data(),
-                uploaded_init_song_info.mime_type.clone(),
-            ),
-        };
+    let (info, skipped, failed) = expand_playlists(&state, info).await;
+    ServerMessage::Batch(BatchInfo {
+        songs: info.len(),
+        skipped: skipped.clone(),
+    })
+    .send(&mut ws)
+    .await?;
 
-        // We parse metadata from song and send back to client
-        let parsed_meta = tokio::task::spawn_blocking({
-            let song_data = song_data.clone();
-            let song = song.clone();
-            move || get_metadata(song_data, &song)
-        })
-        .await
-        .unwrap()?;
-        tracing::debug!(
-            "Parsed meta from song: {:?} from {:?} by {:?}. album_cover? {}",
-            parsed_meta.title.as_deref().unwrap_or_default(),
-            parsed_meta.album.as_deref().unwrap_or_default(),
-            parsed_meta.artists,
-            parsed_meta.album_cover.is_some()
-        );
-        ws.send(extract::ws::Message::Text(
-            serde_json::to_string(&ClientMetadata {
-                album: parsed_meta.album.clone(),
-                artists: parsed_meta.artists.clone(),
-                title: parsed_meta.title.clone(),
-            })?
-            .into(),
-        ))
-        .await?;
-
-        // Client sends back final data for saving song, allow redoing this until successful
+    // Start every import up front, so downloads are ready by the time we get to them
+    let pending = info
+        .iter()
+        .map(|song| state.importers.begin(song, &user))
+        .collect::<Vec<_>>();
+
+    let mut summary = SummaryInfo {
+        skipped: skipped
+            .into_iter()
+            .map(|url| SkippedItem {
+                index: None,
+                name: url,
+            })
+            .collect(),
+        failed,
+        ..Default::default()
+    };
+    for (index, (song, pending)) in info.into_iter().zip(pending).enumerate() {
+        let name = song.display_name();
+        let mut pending = Some(pending);
         loop {
-            let final_meta_str = ws
-                .recv()
-                .await
-                .ok_or(ApiError::InvalidWSMessage)??
-                .into_text()?;
-            let final_meta: FinalMetadata = serde_json::from_str(&final_meta_str)?;
-            tracing::debug!("Got final meta: {final_meta:#?}");
-
-            let res = match add_song(
-                state.clone(),
-                song_data.clone(),
-                mime_type.clone(),
-                final_meta,
-                parsed_meta.album_cover.clone(),
-            )
-            .await
-            {
-                Ok(res) => res,
-                Err(err) => {
-                    // Failed to add song, inform client and let them send final meta again
-                    ws.send(extract::ws::Message::Text(
-                        serde_json::to_string(&Error {
-                            error: format!("{err:?}"),
-                        })?
-                        .into(),
-                    ))
+            let pending = pending
+                .take()
+                .unwrap_or_else(|| state.importers.begin(&song, &user));
+            match import_item(&mut ws, &state, song.clone(), pending).await? {
+                ItemOutcome::Added(song_ids) => {
+                    summary.succeeded.push(SucceededItem {
+                        index,
+                        name,
+                        song_ids,
+                    });
+                }
+                ItemOutcome::Skipped => summary.skipped.push(SkippedItem {
+                    index: Some(index),
+                    name,
+                }),
+                ItemOutcome::Failed {
+                    stage,
+                    error,
+                    retryable,
+                } => {
+                    // Let the client decide what to do, the rest of the batch goes on either way
+                    ServerMessage::ItemFailed(ItemFailure {
+                        index,
+                        name: name.clone(),
+                        stage,
+                        error: error.clone(),
+                        retryable,
+                    })
+                    .send(&mut ws)
                     .await?;
-                    continue;
+                    let ClientMessage::Decision(decision) = ClientMessage::recv(&mut ws).await?
+                    else {
+                        return Err(ApiError::InvalidWSMessage);
+                    };
+                    if retryable && decision == Decision::Retry {
+                        continue;
+                    }
+                    summary.failed.push(FailedItem {
+                        index: Some(index),
+                        name,
+                        error,
+                    });
                 }
-            };
-
-            // We added the song successfully, break this loop and move onto next song
-            ws.send(extract::ws::Message::Text(
-                serde_json::to_string(&res).unwrap().into(),
-            ))
-            .await?;
+            }
             break;
         }
     }
 
+    ServerMessage::Summary(summary).send(&mut ws).await?;
     ws.send(extract::ws::Message::Close(None)).await?;
     Ok(())
 }
 
-async fn add_song(
-    state: State,
-    song_data: Bytes,
+/// Imports one item of the batch. Only errors with the WS itself are returned as errors,
+/// anything wrong with the item is a [`ItemOutcome::Failed`].
+async fn import_item(
+    ws: &mut WebSocket,
+    state: &State,
+    song: Arc<InitSongInfo>,
+    pending: Box<dyn PendingImport>,
+) -> Result<ItemOutcome, ApiError> {
+    // Client sends song data up or the importer downloads it
+    let Imported {
+        staged,
+        hints,
+        provenance,
+    } = match pending.finish(ws).await {
+        Ok(imported) => imported,
+        Err(err) => return Ok(ItemOutcome::failed(ItemStage::Import, err, true)),
+    };
+
+    // A whole album gets confirmed at once
+    if is_archive(&staged.mime_type) {
+        let res = import_album(ws, state, &staged, &song, &provenance).await;
+        staged.remove().await;
+        return Ok(res.unwrap_or_else(|err| ItemOutcome::failed(ItemStage::Album, err, false)));
+    }
+
+    // The client decides whether a file we already have is added again
+    let existing = match find_duplicate(state, &staged).await {
+        Ok(existing) => existing,
+        Err(err) => {
+            staged.remove().await;
+            return Ok(ItemOutcome::failed(ItemStage::Import, err, true));
+        }
+    };
+    let linked = match existing {
+        Some((song_id, source)) => {
+            let title = Song::get_by_id(song_id, &state.sqlite)
+                .await?
+                .map(|song| song.title)
+                .unwrap_or_default();
+            ServerMessage::Duplicate(DuplicateInfo {
+                song_id,
+                title,
+                content_hash: source.content_hash.clone().unwrap_or_default(),
+            })
+            .send(ws)
+            .await?;
+            match ClientMessage::recv(ws).await? {
+                ClientMessage::Decision(Decision::Link) => Some(source),
+                _ => {
+                    staged.remove().await;
+                    return Ok(ItemOutcome::Skipped);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let (mut parsed_meta, decode_report) =
+        match probe_staged(&staged, song, state.config.filename_templates.clone()).await {
+            Ok(probed) => probed,
+            Err(err) => {
+                staged.remove().await;
+                return Ok(ItemOutcome::failed(ItemStage::Probe, err, false));
+            }
+        };
+    hints.apply(&mut parsed_meta);
+    if let Some(problem) = decode_report.problem() {
+        staged.remove().await;
+        return Ok(ItemOutcome::Failed {
+            stage: ItemStage::Corrupt,
+            error: format!("Corrupt audio file: {problem}"),
+            retryable: false,
+        });
+    }
+    tracing::debug!(
+        "Parsed meta from song: {:?} from {:?} by {:?}. album_cover? {}",
+        parsed_meta.title.as_deref().unwrap_or_default(),
+        parsed_meta.album.as_deref().unwrap_or_default(),
+        parsed_meta.artists,
+        parsed_meta.album_cover.is_some()
+    );
+    ServerMessage::Metadata(SongMetadata {
+        album: parsed_meta.album.clone(),
+        artists: parsed_meta.artists.clone(),
+        title: parsed_meta.title.clone(),
+        decode_report,
+        from_filename: parsed_meta.from_filename.clone(),
+    })
+    .send(ws)
+    .await?;
+
+    // Client sends back final data for saving song, allow redoing this until successful
+    let outcome = loop {
+        let final_meta = match ClientMessage::recv(ws).await? {
+            ClientMessage::Decision(_) => break ItemOutcome::Skipped,
+            ClientMessage::Metadata(final_meta) => {
+                FinalMetadata::or_parsed(final_meta, &parsed_meta)
+            }
+            ClientMessage::Replace(_) if linked.is_some() => {
+                ServerMessage::error("A song already has this file, it can only be linked")
+                    .send(ws)
+                    .await?;
+                continue;
+            }
+            ClientMessage::Replace(replace) => {
+                match replace_audio(&state.sqlite, &staged, &parsed_meta, &replace).await {
+                    Ok(res) => {
+                        let song_id = res.song_id;
+                        ServerMessage::Replaced(res).send(ws).await?;
+                        break ItemOutcome::Added(vec![song_id]);
+                    }
+                    Err(err) => {
+                        ServerMessage::Error(err.into()).send(ws).await?;
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                ServerMessage::error("Expected final metadata or a decision")
+                    .send(ws)
+                    .await?;
+                continue;
+            }
+        };
+        tracing::debug!("Got final meta: {final_meta:#?}");
+
+        let song_file = match &linked {
+            Some(source) => SongFile::Existing(source),
+            None => SongFile::Staged(&staged),
+        };
+        let res = match add_song(
+            &state.sqlite,
+            song_file,
+            final_meta,
+            parsed_meta.album_cover.clone(),
+            &provenance,
+        )
+        .await
+        {
+            Ok(res) => res,
+            Err(err) => {
+                // Failed to add song, inform client and let them send final meta again
+                ServerMessage::Error(err.into()).send(ws).await?;
+                continue;
+            }
+        };
+
+        add_hint_tags(state, &hints, res.song_id).await;
+
+        // We added the song successfully, break this loop and move onto next song
+        let song_id = res.song_id;
+        ServerMessage::Added(res).send(ws).await?;
+        break ItemOutcome::Added(vec![song_id]);
+    };
+
+    staged.remove().await;
+    Ok(outcome)
+}
+
+/// A song with a source that has the same content as the staged file
+async fn find_duplicate(
+    state: &State,
+    staged: &StagedFile,
+) -> Result<Option<(i64, Source)>, ApiError> {
+    let content_hash = staged.content_hash().await?;
+    Ok(Source::song_with_hash(content_hash, &state.sqlite).await?)
+}
+
+/// Fields of the form sent to [`upload_song`]
+#[derive(Debug, Default)]
+struct SongForm {
+    file: Option<StagedFile>,
+    file_name: Option<Arc<str>>,
+    title: Option<Arc<str>>,
+    album: Option<Arc<str>>,
+    artists: Vec<Arc<str>>,
+    storage_backend: Option<Arc<str>>,
+    filename_template: Option<FilenameTemplate>,
+}
+
+/// Adds a song in a single request, for scripts and tools that can't have the WS conversation.
+/// Takes a multipart form with the audio in `file` and optional `title`, `album`, `artists`
+/// (repeat it for more than one), `storageBackend` and `filenameTemplate` fields. Anything left out
+/// is taken from the metadata parsed from the file.
+pub async fn upload_song(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+    mut multipart: extract::Multipart,
+) -> Result<Json<AddSongResult>, ApiError> {
+    let user = auth::authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let mut form = SongForm::default();
+    let res = match read_song_form(&mut multipart, &state, &mut form).await {
+        Ok(()) => add_song_form(&state, &form, &user).await,
+        Err(err) => Err(err),
+    };
+
+    // Staged file is only needed until it's in the storage backend
+    if let Some(staged) = form.file.take() {
+        staged.remove().await;
+    }
+
+    res.map(Json)
+}
+
+async fn read_song_form(
+    multipart: &mut extract::Multipart,
+    state: &State,
+    form: &mut SongForm,
+) -> Result<(), ApiError> {
+    while let Some(mut field) = multipart.next_field().await? {
+        match field.name().unwrap_or_default() {
+            "file" => {
+                form.file_name = field.file_name().map(Arc::from);
+                stage_file_field(&mut field, state, &mut form.file).await?;
+            }
+            "title" => form.title = non_empty(field.text().await?),
+            "album" => form.album = non_empty(field.text().await?),
+            "artists" => form.artists.extend(non_empty(field.text().await?)),
+            "storageBackend" => form.storage_backend = non_empty(field.text().await?),
+            "filenameTemplate" => {
+                form.filename_template = non_empty(field.text().await?)
+                    .map(|template| template.parse())
+                    .transpose()
+                    .map_err(ApiError::BadRequest)?;
+            }
+            name => tracing::debug!("Ignoring unknown form field {name:?}"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn add_song_form(
+    state: &State,
+    form: &SongForm,
+    user: &User,
+) -> Result<AddSongResult, ApiError> {
+    let Some(staged) = &form.file else {
+        return Err(ApiError::BadRequest("Missing file field".into()));
+    };
+
+    let info = Arc::new(InitSongInfo::Uploaded(UploadedInitSongInfo {
+        name: form.file_name.clone().unwrap_or_else(|| Arc::from("")),
+        size: tokio::fs::metadata(&staged.path).await?.len() as usize,
+        mime_type: staged.mime_type.clone(),
+        upload_id: None,
+        filename_template: form.filename_template.clone(),
+    }));
+    let (parsed_meta, decode_report) =
+        probe_staged(staged, info, state.config.filename_templates.clone()).await?;
+    if let Some(problem) = decode_report.problem() {
+        return Err(ApiError::BadRequest(format!(
+            "Corrupt audio file: {problem}"
+        )));
+    }
+
+    let final_meta = final_metadata(
+        form.title.clone(),
+        form.album.clone(),
+        form.artists.clone(),
+        form.storage_backend
+            .clone()
+            .unwrap_or_else(default_storage_backend_name),
+        &parsed_meta,
+    )?;
+    tracing::debug!("Adding song from form: {final_meta:#?}");
+
+    add_song(
+        &state.sqlite,
+        SongFile::Staged(staged),
+        final_meta,
+        parsed_meta.album_cover,
+        &SongProvenance::new(
+            ImportMethod::Form,
+            Some(&user.username),
+            form.file_name.as_deref(),
+        ),
+    )
+    .await
+}
+
+/// Fills in whatever the client left out with metadata parsed from the file
+pub fn final_metadata(
+    title: Option<Arc<str>>,
+    album: Option<Arc<str>>,
+    artists: Vec<Arc<str>>,
+    storage_backend: Arc<str>,
+    parsed_meta: &ParsedMetadata,
+) -> Result<FinalMetadata, ApiError> {
+    let Some(title) = title.or_else(|| parsed_meta.title.clone()) else {
+        return Err(ApiError::BadRequest(
+            "Couldn't find a title in the file, send one in the title field".into(),
+        ));
+    };
+    let artists = if artists.is_empty() {
+        parsed_meta.artists.clone()
+    } else {
+        artists
+    };
+
+    Ok(FinalMetadata {
+        title,
+        album: album.or_else(|| parsed_meta.album.clone()),
+        artists: artists.iter().map(|artist| Box::from(&**artist)).collect(),
+        storage_backend,
+        album_artist: parsed_meta.album_artist.clone(),
+        track: parsed_meta.track,
+        disc: parsed_meta.disc,
+    })
+}
+
+/// Streams an audio file from a form into a staged file. It's kept in `file` right away so it
+/// gets removed if anything fails.
+pub async fn stage_file_field(
+    field: &mut extract::multipart::Field<'_>,
+    state: &State,
+    file: &mut Option<StagedFile>,
+) -> Result<(), ApiError> {
+    let mime_type = field.content_type().unwrap_or_default();
+    if !ALLOWED_MIME_TYPES.contains(&mime_type) {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid mime type: {mime_type}"
+        )));
+    }
+    if file.is_some() {
+        return Err(ApiError::BadRequest("Only one file can be sent".into()));
+    }
+
+    let (staged, mut writer) =
+        StagedFile::create(&state.config.data_dir, Arc::from(mime_type)).await?;
+    *file = Some(staged);
+    while let Some(chunk) = field.chunk().await? {
+        writer.write_all(&chunk).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+pub fn non_empty(value: String) -> Option<Arc<str>> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| Arc::from(value))
+}
+
+/// Parses metadata from the song and decodes all of it to make sure it isn't corrupt
+pub async fn probe_staged(
+    staged: &StagedFile,
+    info: Arc<InitSongInfo>,
+    filename_templates: Arc<[FilenameTemplate]>,
+) -> Result<(ParsedMetadata, DecodeReport), ApiError> {
+    let meta_reader = tokio::fs::File::open(&staged.path).await?.into_std().await;
+    let verify_reader = tokio::fs::File::open(&staged.path).await?.into_std().await;
+    probe(
+        Box::new(meta_reader),
+        Box::new(verify_reader),
+        info,
+        staged.mime_type.clone(),
+        filename_templates,
+    )
+    .await
+}
+
+/// Parses metadata with one reader and decodes everything with the other, since each pass
+/// needs to start from the beginning of the song
+pub async fn probe(
+    meta_reader: Box<dyn MediaSource>,
+    verify_reader: Box<dyn MediaSource>,
+    info: Arc<InitSongInfo>,
     mime_type: Arc<str>,
+    filename_templates: Arc<[FilenameTemplate]>,
+) -> Result<(ParsedMetadata, DecodeReport), ApiError> {
+    let (parsed_meta, decode_report) = tokio::task::spawn_blocking(move || {
+        get_metadata(meta_reader, &info, &filename_templates)
+            .map(|meta| (meta, verify_audio(verify_reader, Some(&mime_type))))
+    })
+    .await
+    .unwrap()?;
+    tracing::debug!("Decode report: {decode_report:?}");
+
+    Ok((parsed_meta, decode_report))
+}
+
+/// Extracts an album uploaded as an archive and adds all of its tracks after the client
+/// confirms metadata for the whole album
+async fn import_album(
+    ws: &mut WebSocket,
+    state: &State,
+    archive: &StagedFile,
+    info: &InitSongInfo,
+    provenance: &SongProvenance,
+) -> Result<ItemOutcome, ApiError> {
+    let album = ExtractedAlbum::extract(&state.config.data_dir, archive).await?;
+    let res = import_album_tracks(ws, state, &album, info, provenance).await;
+    album.remove().await;
+    res
+}
+
+async fn import_album_tracks(
+    ws: &mut WebSocket,
+    state: &State,
+    album: &ExtractedAlbum,
+    info: &InitSongInfo,
+    provenance: &SongProvenance,
+) -> Result<ItemOutcome, ApiError> {
+    let mut probed = Vec::with_capacity(album.tracks.len());
+    for (name, staged) in &album.tracks {
+        // Title falls back to the track's file name
+        let stem = Path::new(&**name)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let track_info = Arc::new(InitSongInfo::Uploaded(UploadedInitSongInfo {
+            name: Arc::from(stem),
+            size: 0,
+            mime_type: staged.mime_type.clone(),
+            upload_id: None,
+            filename_template: info.filename_template().cloned(),
+        }));
+        let templates = state.config.filename_templates.clone();
+        probed.push(probe_staged(staged, track_info, templates).await?);
+    }
+
+    ServerMessage::Archive(ArchiveInfo {
+        album: probed.iter().find_map(|(parsed, _)| parsed.album.clone()),
+        artists: probed
+            .iter()
+            .map(|(parsed, _)| &parsed.artists)
+            .find(|artists| !artists.is_empty())
+            .cloned()
+            .unwrap_or_default(),
+        has_cover: album.cover.is_some(),
+        tracks: album
+            .tracks
+            .iter()
+            .zip(&probed)
+            .map(|((name, _), (parsed, decode_report))| TrackMetadata {
+                name: name.clone(),
+                meta: SongMetadata {
+                    title: parsed.title.clone(),
+                    album: parsed.album.clone(),
+                    artists: parsed.artists.clone(),
+                    decode_report: decode_report.clone(),
+                    from_filename: parsed.from_filename.clone(),
+                },
+            })
+            .collect(),
+    })
+    .send(ws)
+    .await?;
+
+    // Allow redoing this until every track is added, without adding any twice
+    let mut added = album.tracks.iter().map(|_| None).collect::<Vec<_>>();
+    loop {
+        let final_meta = match ClientMessage::recv(ws).await? {
+            ClientMessage::Decision(_) => break,
+            ClientMessage::Album(final_meta) => final_meta,
+            _ => {
+                ServerMessage::error("Expected final album metadata or a decision")
+                    .send(ws)
+                    .await?;
+                continue;
+            }
+        };
+        tracing::debug!("Got final album meta: {final_meta:#?}");
+
+        let mut error = None;
+        for (i, ((name, staged), (parsed, decode_report))) in
+            album.tracks.iter().zip(&probed).enumerate()
+        {
+            if added[i].is_some() {
+                continue;
+            }
+            if let Some(problem) = decode_report.problem() {
+                tracing::warn!("Skipping corrupt track {name}: {problem}");
+                continue;
+            }
+
+            let track = final_meta.tracks.get(i);
+            let artists = [
+                track.map(|track| track.artists.clone()).unwrap_or_default(),
+                final_meta.artists.clone(),
+            ]
+            .into_iter()
+            .find(|artists| !artists.is_empty())
+            .unwrap_or_default();
+            let provenance = SongProvenance {
+                original_name: Some(match &provenance.original_name {
+                    Some(archive_name) => format!("{archive_name}/{name}"),
+                    None => name.to_string(),
+                }),
+                ..provenance.clone()
+            };
+
+            let res = match final_metadata(
+                track.and_then(|track| track.title.clone()),
+                final_meta.album.clone(),
+                artists,
+                final_meta.storage_backend.clone(),
+                parsed,
+            ) {
+                Ok(track_meta) => {
+                    add_song(
+                        &state.sqlite,
+                        SongFile::Staged(staged),
+                        track_meta,
+                        album.cover.clone().or_else(|| parsed.album_cover.clone()),
+                        &provenance,
+                    )
+                    .await
+                }
+                Err(err) => Err(err),
+            };
+            match res {
+                Ok(res) => added[i] = Some(res),
+                Err(err) => {
+                    let err = ErrorInfo::from(err);
+                    error = Some(ErrorInfo {
+                        message: format!("{name}: {}", err.message),
+                        ..err
+                    });
+                    break;
+                }
+            }
+        }
+
+        if let Some(error) = error {
+            // Inform client and let them send final meta again
+            ServerMessage::Error(error).send(ws).await?;
+            continue;
+        }
+
+        let songs = added.into_iter().flatten().collect::<Vec<_>>();
+        let song_ids = songs.iter().map(|song| song.song_id).collect();
+        ServerMessage::Songs(songs).send(ws).await?;
+        return Ok(ItemOutcome::Added(song_ids));
+    }
+
+    // Tracks added before the client gave up on the rest are kept
+    let song_ids = added
+        .iter()
+        .flatten()
+        .map(|song| song.song_id)
+        .collect::<Vec<_>>();
+    Ok(if song_ids.is_empty() {
+        ItemOutcome::Skipped
+    } else {
+        ItemOutcome::Added(song_ids)
+    })
+}
+
+/// Replaces playlist and channel urls with their videos, leaving out videos that were
+/// already imported. Returns the songs to import, the urls that were skipped and the urls
+/// that couldn't be expanded, which don't stop the rest of the batch.
+async fn expand_playlists(
+    state: &State,
+    info: Box<[Arc<InitSongInfo>]>,
+) -> (Vec<Arc<InitSongInfo>>, Vec<Arc<str>>, Vec<FailedItem>) {
+    let mut expanded = Vec::with_capacity(info.len());
+    let mut skipped = Vec::new();
+    let mut failed = Vec::new();
+
+    for song in info {
+        let InitSongInfo::Yt(yt_init_song_info) = &*song else {
+            expanded.push(song);
+            continue;
+        };
+
+        if let Err(err) =
+            expand_playlist(state, yt_init_song_info, &mut expanded, &mut skipped).await
+        {
+            tracing::warn!("Couldn't expand {}: {err}", yt_init_song_info.url);
+            failed.push(FailedItem {
+                index: None,
+                name: song.display_name(),
+                error: format!("{err}"),
+            });
+        }
+    }
+
+    (expanded, skipped, failed)
+}
+
+async fn expand_playlist(
+    state: &State,
+    yt_init_song_info: &YtInitSongInfo,
+    expanded: &mut Vec<Arc<InitSongInfo>>,
+    skipped: &mut Vec<Arc<str>>,
+) -> Result<(), ApiError> {
+    if !is_collection_url(&yt_init_song_info.url) {
+        // Single videos don't need yt-dlp, only a check that they weren't imported already
+        let video_id = video_id_from_url(&yt_init_song_info.url);
+        if let Some(video_id) = &video_id
+            && !SongProvenance::imported_video_ids(&[&**video_id], &state.sqlite)
+                .await?
+                .is_empty()
+        {
+            skipped.push(yt_init_song_info.url.clone());
+            return Ok(());
+        }
+
+        expanded.push(Arc::new(InitSongInfo::Yt(YtInitSongInfo {
+            video_id,
+            ..yt_init_song_info.clone()
+        })));
+        return Ok(());
+    }
+
+    let playlist = state.yt_dlp.expand(&yt_init_song_info.url).await?;
+    let ids = playlist
+        .entries
+        .iter()
+        .map(|entry| &*entry.id)
+        .collect::<Vec<_>>();
+    let mut imported = SongProvenance::imported_video_ids(&ids, &state.sqlite).await?;
+    // Some collection-looking urls still turn out to be a single video, which has no title
+    let playlist_url = playlist
+        .title
+        .is_some()
+        .then(|| yt_init_song_info.url.clone());
+    let tag = playlist
+        .title
+        .filter(|_| yt_init_song_info.playlist_as_tag);
+
+    for entry in playlist.entries {
+        // Also catches videos that are in a playlist more than once
+        if !imported.insert(entry.id.to_string()) {
+            skipped.push(entry.url);
+            continue;
+        }
+
+        expanded.push(Arc::new(InitSongInfo::Yt(YtInitSongInfo {
+            url: entry.url,
+            playlist_as_tag: false,
+            options: yt_init_song_info.options.clone(),
+            video_id: Some(entry.id),
+            playlist_url: playlist_url.clone(),
+            tag: tag.clone(),
+        })));
+    }
+
+    Ok(())
+}
+
+/// Adds the tags the importer gave, like the playlist a video was in
+async fn add_hint_tags(state: &State, hints: &MetadataHints, song_id: i64) {
+    for tag in &hints.tags {
+        let res = match Tag::insert_or_ignore(tag, &state.sqlite).await {
+            Ok(()) => Song::add_tag(song_id, tag, &state.sqlite).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            tracing::error!("Couldn't add tag {tag} to song {song_id}: {err:?}");
+        }
+    }
+}
+
+pub async fn add_song(
+    sqlite: &Pool<Sqlite>,
+    song: SongFile<'_>,
     final_meta: FinalMetadata,
     album_cover: Option<AlbumCover>,
+    provenance: &SongProvenance,
 ) -> Result<AddSongResult, ApiError> {
-    let storage_backend = StorageBackend::get_by_name(&final_meta.storage_backend, &state.sqlite)
+    let storage_backend = StorageBackend::get_by_name(&final_meta.storage_backend, sqlite)
         .await?
         .ok_or(ApiError::NotFound)?;
     let operator = storage_backend.operator().await?;
-    let path = format!(
-        "songs/{}-{}.{}",
-        final_meta.title.replace("/", "~slash~"),
-        chrono::Utc::now().timestamp(),
-        mime_type.split_once("/").unwrap().1
-    );
+    let mime_type = match song {
+        SongFile::Staged(staged) => &*staged.mime_type,
+        SongFile::InBackend { mime_type, .. } | SongFile::InPlace { mime_type, .. } => mime_type,
+        SongFile::Existing(source) => &source.mime_type,
+    };
+    // Hashed before it's moved, so nothing needs to be undone if reading it fails
+    let content_hash = match song {
+        SongFile::Staged(staged) => Some(staged.content_hash().await?.to_string()),
+        SongFile::InBackend { key: path, .. } | SongFile::InPlace { path, .. } => {
+            Some(content_hash::hash_object(&operator, path).await?)
+        }
+        SongFile::Existing(source) => source.content_hash.clone(),
+    };
+    let path = match song {
+        SongFile::InPlace { path, .. } => path.to_string(),
+        SongFile::Existing(source) => source.path.clone(),
+        _ => {
+            let path = storage_backend.config.path_template().render(&PathFields {
+                title: &final_meta.title,
+                album: final_meta.album.as_deref(),
+                album_artist: final_meta.album_artist.as_deref(),
+                artist: final_meta.artists.first().map(|artist| &**artist),
+                disc: final_meta.disc,
+                track: final_meta.track,
+                mime_type,
+                timestamp: chrono::Utc::now().timestamp(),
+            });
+            unused_path(&operator, path, None).await?
+        }
+    };
+    // Albums keep the first cover they got
+    let album_cover = match (&final_meta.album, album_cover) {
+        (Some(album_title), Some(album_cover))
+            if !Album::has_cover(album_title, sqlite).await? =>
+        {
+            let path = format!(
+                "images/{}.{}",
+                album_title.replace("/", "~slash~"),
+                extension_for_mime_type(&album_cover.mime_type)
+            );
+            Some((unused_path(&operator, path, None).await?, album_cover))
+        }
+        _ => None,
+    };
 
     // Write to storage backend first since its waaaaaay more likely to fail
-    let _ = operator.write(&path, song_data).await?;
-    let song_id = Song::insert_w_source(
-        &final_meta.title,
-        &path,
-        &mime_type,
-        &final_meta.storage_backend,
-        &state.sqlite,
-    )
-    .await?;
-    let mut res = AddSongResult::default();
+    let written = match song {
+        SongFile::Staged(staged) => staged.write_to(&operator, &path).await,
+        SongFile::InBackend { key, .. } => move_object(&operator, key, &path)
+            .await
+            .map_err(Into::into),
+        SongFile::InPlace { .. } | SongFile::Existing(_) => Ok(()),
+    };
+    if let Err(err) = written {
+        // A partly written file could be there
+        let left_behind = match song {
+            SongFile::Staged(_) => roll_back_objects(&operator, song, &path, None).await,
+            _ => Vec::new(),
+        };
+        return Err(add_song_failed(AddSongStep::WriteSong, err, left_behind));
+    }
+    if let Some((cover_path, album_cover)) = &album_cover
+        && let Err(err) = operator.write(cover_path, album_cover.data.clone()).await
+    {
+        let left_behind = roll_back_objects(&operator, song, &path, None).await;
+        return Err(add_song_failed(
+            AddSongStep::WriteCover,
+            err.into(),
+            left_behind,
+        ));
+    }
 
-    // Create & add album tag to song
-    if let Some(album_title) = final_meta.album {
-        let album_tag_res = if let Some(album_cover) = album_cover {
-            // TODO: album cover from song metadata, needs some image encoding/decoding stuff and operator
+    let cover = album_cover
+        .as_ref()
+        .map(|(cover_path, album_cover)| (&**cover_path, &*album_cover.mime_type));
+    let source = match song {
+        SongFile::Existing(source) => NewSongSource::Existing(source.id),
+        _ => NewSongSource::New {
+            path: &path,
+            mime_type,
+            content_hash: content_hash.as_deref(),
+            in_place: matches!(song, SongFile::InPlace { .. }),
+        },
+    };
+    match insert_song(sqlite, &final_meta, source, cover, provenance).await {
+        Ok(res) => Ok(res),
+        Err((step, err)) => {
+            let cover_path = cover.map(|(cover_path, _)| cover_path);
+            let left_behind = roll_back_objects(&operator, song, &path, cover_path).await;
+            Err(add_song_failed(step, err.into(), left_behind))
+        }
+    }
+}
 
-            let cover_image_mime_type = &*album_cover.mime_type;
-            let cover_image_path = format!(
-                "images/{}.{}",
-                album_title.replace("/", "~slash~"),
-                cover_image_mime_type.split_once("/").unwrap().1
-            );
-            let write_image_res = operator.write(&cover_image_path, album_cover.data).await;
-            if let Err(err) = write_image_res {
-                Err(db::Error::Insert(
-                    "albums",
-                    sqlx::Error::Io(std::io::Error::other(format!(
-                        "couldn't write album cover to backend: {err:?}"
-                    ))),
-                ))
-            } else {
+/// Adds the song and everything about it to the db in one transaction,
+/// so nothing is left behind if any of it fails
+async fn insert_song(
+    sqlite: &Pool<Sqlite>,
+    final_meta: &FinalMetadata,
+    source: NewSongSource<'_>,
+    cover: Option<(&str, &str)>,
+    provenance: &SongProvenance,
+) -> Result<AddSongResult, (AddSongStep, db::Error)> {
+    let mut transaction = sqlite
+        .begin()
+        .await
+        .map_err(|e| (AddSongStep::InsertSong, db::Error::Transaction("songs", e)))?;
+
+    let song_id = match source {
+        NewSongSource::New {
+            path,
+            mime_type,
+            content_hash,
+            in_place,
+        } => {
+            Song::insert_w_source(
+                &final_meta.title,
+                path,
+                mime_type,
+                &final_meta.storage_backend,
+                content_hash,
+                in_place,
+                &mut *transaction,
+            )
+            .await
+        }
+        NewSongSource::Existing(source_id) => {
+            Song::insert_w_existing_source(&final_meta.title, source_id, &mut *transaction).await
+        }
+    }
+    .map_err(|e| (AddSongStep::InsertSong, e))?;
+    let mut res = AddSongResult {
+        song_id,
+        ..Default::default()
+    };
+
+    let provenance = SongProvenance {
+        song_id,
+        ..provenance.clone()
+    };
+    provenance
+        .insert(&mut *transaction)
+        .await
+        .map_err(|e| (AddSongStep::RecordProvenance, e))?;
+
+    if provenance.method.needs_review() {
+        PendingReview {
+            song_id,
+            title: final_meta.title.to_string(),
+            album: final_meta.album.as_deref().map(str::to_string),
+            artists: final_meta.artists.iter().map(|a| a.to_string()).collect(),
+            album_artist: final_meta.album_artist.as_deref().map(str::to_string),
+            track: final_meta.track.map(i64::from),
+            disc: final_meta.disc.map(i64::from),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+        .insert(&mut *transaction)
+        .await
+        .map_err(|e| (AddSongStep::QueueReview, e))?;
+    }
+
+    // Create & add album tag to song
+    if let Some(album_title) = &final_meta.album {
+        let album_tag = match cover {
+            Some((cover_path, cover_mime_type)) => {
                 Album::insert_w_source_and_tag(
-                    &album_title,
-                    &cover_image_path,
-                    cover_image_mime_type,
+                    album_title,
+                    cover_path,
+                    cover_mime_type,
                     &final_meta.storage_backend,
-                    &state.sqlite,
+                    &mut *transaction,
                 )
                 .await
             }
-        } else {
-            Album::insert_w_tag(&album_title, &state.sqlite).await
-        };
+            None => Album::insert_w_tag(album_title, &mut *transaction).await,
+        }
+        .map_err(|e| (AddSongStep::AddAlbum, e))?;
+        res.created_album = Some(true);
 
-        match album_tag_res {
-            Ok(album_tag) => {
-                res.created_album = Some(true);
-                Song::add_tag(song_id, album_tag, &state.sqlite)
-                    .await
-                    .inspect(|_| res.added_album = Some(true))
-                    .inspect_err(|err| {
-                        res.added_album = Some(false);
-                        tracing::error!("Error adding album to song: {err:?}")
-                    })
-                    .ok();
-            }
-            Err(err) => {
-                res.added_album = Some(false);
-                tracing::error!("Error creating album: {err:?}");
-            }
+        if let Some(link) = &provenance.playlist_url {
+            Album::fill_link(album_tag, link, &mut *transaction)
+                .await
+                .map_err(|e| (AddSongStep::AddAlbum, e))?;
         }
+        Song::add_tag(song_id, album_tag, &mut *transaction)
+            .await
+            .map_err(|e| (AddSongStep::AddAlbum, e))?;
+        res.added_album = Some(true);
     }
 
     // Create and add artist tags to song
     if !final_meta.artists.is_empty() {
         let artists_slice = final_meta.artists.iter().map(|s| &**s).collect::<Vec<_>>();
-        match Artist::insert_w_tags(&artists_slice, &state.sqlite).await {
-            Ok(tags) => {
-                res.created_artists = Some(true);
-                Song::add_tags(song_id, tags, &state.sqlite)
-                    .await
-                    .inspect(|_| res.added_artists = Some(true))
-                    .inspect_err(|err| {
-                        res.added_artists = Some(false);
-                        tracing::error!("Error adding artists to song: {err:?}")
-                    })
-                    .ok();
-            }
-            Err(err) => {
-                res.created_artists = Some(false);
-                tracing::error!("Error creating artist tags: {err:?}");
-            }
-        };
+        let tags = Artist::insert_w_tags(&artists_slice, &mut *transaction)
+            .await
+            .map_err(|e| (AddSongStep::AddArtists, e))?;
+        res.created_artists = Some(true);
+
+        if let Some(link) = &provenance.channel_url {
+            Artist::fill_links(tags, link, &mut *transaction)
+                .await
+                .map_err(|e| (AddSongStep::AddArtists, e))?;
+        }
+        Song::add_tags(song_id, tags, &mut *transaction)
+            .await
+            .map_err(|e| (AddSongStep::AddArtists, e))?;
+        res.added_artists = Some(true);
     }
 
+    transaction
+        .commit()
+        .await
+        .map_err(|e| (AddSongStep::Commit, db::Error::Transaction("songs", e)))?;
+
     Ok(res)
 }
 
+/// Undoes what was written to the storage backend for a song that couldn't be added,
+/// returns the paths that are still there
+async fn roll_back_objects(
+    operator: &Operator,
+    song: SongFile<'_>,
+    path: &str,
+    cover_path: Option<&str>,
+) -> Vec<String> {
+    let mut left_behind = Vec::new();
+    let res = match song {
+        SongFile::Staged(_) => operator.delete(path).await,
+        // Put it back so the upload can be completed again
+        SongFile::InBackend { key, .. } => move_object(operator, path, key).await,
+        SongFile::InPlace { .. } | SongFile::Existing(_) => Ok(()),
+    };
+    if let Err(err) = res {
+        tracing::error!("Couldn't roll back {path}: {err:?}");
+        left_behind.push(path.to_string());
+    }
+
+    if let Some(cover_path) = cover_path
+        && let Err(err) = operator.delete(cover_path).await
+    {
+        tracing::error!("Couldn't roll back {cover_path}: {err:?}");
+        left_behind.push(cover_path.to_string());
+    }
+
+    left_behind
+}
+
+fn add_song_failed(step: AddSongStep, err: ApiError, left_behind: Vec<String>) -> ApiError {
+    ApiError::AddSong(Box::new(AddSongFailure {
+        step,
+        error: format!("{err}"),
+        left_behind,
+    }))
+}
+
 async fn close_with_error(mut ws: WebSocket, error: String) -> Result<(), ApiError> {
-    ws.send(extract::ws::Message::Text(
-        serde_json::to_string(&Error { error })?.into(),
-    ))
-    .await?;
+    ServerMessage::error(error).send(&mut ws).await?;
     ws.send(extract::ws::Message::Close(None))
         .await
         .map_err(Into::into)
 }
 
-fn default_storage_backend_name() -> Arc<str> {
+pub fn default_storage_backend_name() -> Arc<str> {
     Arc::from("init")
 }
-
-/// Uses yt-dlp to download the song and returns its raw bytes and (guessed) mime type
-async fn yt_dlp_song(info: &YtInitSongInfo, cookies_path: &str) -> Result<(Bytes, Arc<str>), ApiError> {
-    tracing::debug!("using yt-dlp for {info:?}");
-    tokio::fs::remove_file("/tmp/tmp.opus").await.ok();
-    let has_cookies_file = tokio::fs::try_exists(cookies_path).await?;
-    let mut command = tokio::process::Command::new("yt-dlp");
-    command.args([
-        "-x",
-        "--audio-format",
-        "opus", // Consider making this configurable (I only care about opus honestly)
-        "--audio-quality",
-        "0",
-        "--embed-metadata",
-        "--embed-thumbnail",
-        "--sleep-requests",
-        "0.75",
-        "-o",
-        "/tmp/tmp.opus",
-    ]);
-
-    if has_cookies_file {
-        command.args(["--cookies", cookies_path]);
-    }
-
-    let output = command
-        .arg(&*info.url)
-        .output()
-        .await?;
-
-    if !output.status.success() {
-        tracing::error!(
-            "Failed to download audio for \"{}\": {}",
-            info.url,
-            String::from_utf8_lossy(&output.stderr)
-        );
-        return Err(ApiError::InvalidWSMessage);
-    }
-
-    let data = tokio::fs::read("/tmp/tmp.opus").await?;
-
-    Ok((Bytes::from(data), Arc::from("audio/ogg")))
-}
@@ -0,0 +1,224 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use axum::body::Bytes;
+
+use crate::ApiError;
+
+use super::{
+    audio::{AlbumCover, mime_type_from_extension},
+    upload::{MAX_UPLOAD_SIZE, StagedFile, new_id, uploads_dir},
+};
+
+/// Archives an album can be uploaded as, in one go
+pub const ARCHIVE_MIME_TYPES: [&str; 4] = [
+    "application/zip",
+    "application/x-zip-compressed",
+    "application/x-tar",
+    "application/tar",
+];
+
+/// Stop extracting once this much was written, in case of a zip bomb
+const MAX_EXTRACTED_SIZE: u64 = 2 * MAX_UPLOAD_SIZE as u64;
+/// Image files used as the album's cover, best first
+const COVER_NAMES: [&str; 2] = ["cover", "folder"];
+const MAX_COVER_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Audio and cover of an album that was uploaded as an archive
+#[derive(Debug)]
+pub struct ExtractedAlbum {
+    /// Name of each track in the archive and its extracted file, by the numbers they're named with
+    pub tracks: Vec<Track>,
+    pub cover: Option<AlbumCover>,
+    dir: PathBuf,
+}
+
+/// Cover found so far and which of [`COVER_NAMES`] it was
+type FoundCover = Option<(usize, AlbumCover)>;
+/// Name of a track in the archive and its extracted file
+type Track = (Arc<str>, StagedFile);
+
+pub fn is_archive(mime_type: &str) -> bool {
+    ARCHIVE_MIME_TYPES.contains(&mime_type)
+}
+
+impl ExtractedAlbum {
+    /// Extracts the audio files and cover into a staging dir of their own,
+    /// anything else in the archive is ignored
+    pub async fn extract(data_dir: &Path, archive: &StagedFile) -> Result<Self, ApiError> {
+        let dir = uploads_dir(data_dir).join(&*new_id());
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let res = tokio::task::spawn_blocking({
+            let dir = dir.clone();
+            let path = archive.path.clone();
+            let mime_type = archive.mime_type.clone();
+            move || extract(&path, &mime_type, &dir)
+        })
+        .await
+        .unwrap();
+
+        match res {
+            Ok((tracks, cover)) if !tracks.is_empty() => Ok(Self {
+                tracks,
+                cover: cover.map(|(_, cover)| cover),
+                dir,
+            }),
+            res => {
+                if let Err(err) = tokio::fs::remove_dir_all(&dir).await {
+                    tracing::error!("Couldn't remove staging dir {}: {err:?}", dir.display());
+                }
+                res.and(Err(ApiError::BadRequest(
+                    "No audio files in the archive".into(),
+                )))
+            }
+        }
+    }
+
+    pub async fn remove(self) {
+        if let Err(err) = tokio::fs::remove_dir_all(&self.dir).await {
+            tracing::error!(
+                "Couldn't remove staging dir {}: {err:?}",
+                self.dir.display()
+            );
+        }
+    }
+}
+
+fn extract(path: &Path, mime_type: &str, dir: &Path) -> Result<(Vec<Track>, FoundCover), ApiError> {
+    let mut extractor = Extractor {
+        dir,
+        written: 0,
+        tracks: Vec::new(),
+        cover: None,
+    };
+    let file = fs::File::open(path)?;
+
+    if mime_type.contains("zip") {
+        let mut archive = zip::ZipArchive::new(file).map_err(invalid_archive)?;
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i).map_err(invalid_archive)?;
+            if entry.is_file() {
+                let name = entry.name().to_string();
+                extractor.entry(&name, &mut entry)?;
+            }
+        }
+    } else {
+        let mut archive = tar::Archive::new(file);
+        for entry in archive.entries().map_err(invalid_archive)? {
+            let mut entry = entry.map_err(invalid_archive)?;
+            if entry.header().entry_type().is_file() {
+                let name = entry
+                    .path()
+                    .map_err(invalid_archive)?
+                    .to_string_lossy()
+                    .into_owned();
+                extractor.entry(&name, &mut entry)?;
+            }
+        }
+    }
+
+    // Track numbers usually lead the names, but aren't always padded
+    extractor
+        .tracks
+        .sort_by(|(a, _), (b, _)| track_order(a).cmp(&track_order(b)));
+    Ok((extractor.tracks, extractor.cover))
+}
+
+/// Sorts by folder first for albums split into discs, then by the number the file name starts
+/// with. Names without one go after the numbered ones.
+fn track_order(name: &str) -> (&str, u64, &str) {
+    let path = Path::new(name);
+    let dir = path
+        .parent()
+        .and_then(|dir| dir.to_str())
+        .unwrap_or_default();
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(name);
+    let digits = file_name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(file_name.len());
+    let number = file_name[..digits].parse().unwrap_or(u64::MAX);
+    (dir, number, name)
+}
+
+struct Extractor<'a> {
+    dir: &'a Path,
+    written: u64,
+    tracks: Vec<Track>,
+    cover: FoundCover,
+}
+
+impl Extractor<'_> {
+    fn entry(&mut self, name: &str, reader: &mut dyn Read) -> Result<(), ApiError> {
+        // Never use the entry's path for anything but its name, it could point anywhere
+        let entry_path = Path::new(name);
+        let Some(file_name) = entry_path.file_name().map(|name| name.to_string_lossy()) else {
+            return Ok(());
+        };
+        // Hidden files and macOS resource forks aren't real tracks
+        if file_name.starts_with('.') || name.contains("__MACOSX") {
+            return Ok(());
+        }
+        let stem = entry_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        let extension = entry_path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+
+        if let Some(mime_type) = mime_type_from_extension(&extension) {
+            let path = self.dir.join(format!("{}.{extension}", self.tracks.len()));
+            let mut file = fs::File::create(&path)?;
+            let limit = MAX_EXTRACTED_SIZE - self.written;
+            self.written += io::copy(&mut reader.take(limit + 1), &mut file)?;
+            if self.written > MAX_EXTRACTED_SIZE {
+                return Err(ApiError::BadRequest("Archive is too big to extract".into()));
+            }
+
+            self.tracks.push((
+                Arc::from(name),
+                StagedFile::external(path, Arc::from(mime_type)),
+            ));
+        } else if let Some(rank) = COVER_NAMES.iter().position(|cover| *cover == stem) {
+            let cover_mime_type = match &*extension {
+                "jpg" | "jpeg" => "image/jpeg",
+                "png" => "image/png",
+                _ => return Ok(()),
+            };
+            if self.cover.as_ref().is_some_and(|(found, _)| *found <= rank) {
+                return Ok(());
+            }
+
+            let mut data = Vec::new();
+            reader.take(MAX_COVER_SIZE + 1).read_to_end(&mut data)?;
+            if data.len() as u64 > MAX_COVER_SIZE {
+                tracing::warn!("Ignoring cover {name}, it's too big");
+                return Ok(());
+            }
+            self.cover = Some((
+                rank,
+                AlbumCover {
+                    data: Bytes::from(data),
+                    mime_type: Arc::from(cover_mime_type),
+                },
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid_archive(err: impl std::fmt::Display) -> ApiError {
+    ApiError::BadRequest(format!("Invalid archive: {err}"))
+}
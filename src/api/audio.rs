@@ -1,33 +1,47 @@
-use std::{io::Cursor, sync::Arc};
+use std::sync::Arc;
 
 use axum::{Json, body::Bytes, extract};
 use axum_extra::extract::CookieJar;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use symphonia::core::{
+    codecs::{CODEC_TYPE_NULL, DecoderOptions},
+    errors::Error as SymphoniaError,
     formats::FormatOptions,
-    io::MediaSourceStream,
+    io::{MediaSource, MediaSourceStream},
     meta::{MetadataOptions, MetadataRevision, StandardTagKey, StandardVisualKey, Tag},
     probe::Hint,
 };
 
 use crate::{
     ApiError,
-    db::{Album, Song, Source, StorageBackend},
+    db::SourceVerification,
+    yt_dlp::YtDlpOverrides,
 };
 
 use super::{
     State,
     auth::{AUTH_COOKIE, authenticate},
+    filename_template::{FilenameFields, FilenameTemplate},
 };
 
 pub const ALLOWED_COVER_IMAGE_MIME_TYPES: [&str; 3] = ["image/jpeg", "image/jpg", "image/png"];
 
+/// Only keep this many decode error messages in a report
+const MAX_REPORTED_DECODE_ERRORS: usize = 16;
+/// Allowed difference between the declared and decoded duration before a file counts as truncated
+const MAX_DURATION_MISMATCH_SECS: f64 = 1.0;
+
 #[derive(Debug)]
 pub struct ParsedMetadata {
     pub title: Option<Arc<str>>,
     pub album: Option<Arc<str>>,
     pub artists: Vec<Arc<str>>,
     pub album_cover: Option<AlbumCover>,
+    pub album_artist: Option<Arc<str>>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    /// Fields found in the file's name, when its tags were missing some
+    pub from_filename: Option<FilenameFields>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,42 +50,197 @@ pub struct AlbumCover {
     pub mime_type: Arc<str>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Result of decoding every packet of a song
+#[derive(Debug, Clone, Default, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/DecodeReport.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeReport {
+    /// Seconds of audio that actually decoded
+    pub duration: f64,
+    /// Seconds of audio the container claims to have
+    pub declared_duration: Option<f64>,
+    #[ts(type = "number")]
+    pub packets: u64,
+    /// Packets that failed to decode
+    #[ts(type = "number")]
+    pub lost_packets: u64,
+    pub errors: Vec<String>,
+    /// Error that stopped decoding early, if any
+    pub fatal_error: Option<String>,
+}
+
+impl DecodeReport {
+    pub fn is_corrupt(&self) -> bool {
+        self.fatal_error.is_some()
+            || self.packets == 0
+            || self.lost_packets > 0
+            || self.is_truncated()
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.declared_duration
+            .is_some_and(|declared| declared - self.duration > MAX_DURATION_MISMATCH_SECS)
+    }
+
+    /// Short human readable reason this report counts as corrupt
+    pub fn problem(&self) -> Option<String> {
+        if let Some(fatal_error) = &self.fatal_error {
+            Some(fatal_error.clone())
+        } else if self.packets == 0 {
+            Some("no audio packets".into())
+        } else if self.lost_packets > 0 {
+            Some(format!(
+                "{} of {} packets failed to decode",
+                self.lost_packets, self.packets
+            ))
+        } else if self.is_truncated() {
+            Some(format!(
+                "truncated, decoded {:.1}s of {:.1}s",
+                self.duration,
+                self.declared_duration.unwrap_or_default()
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn into_verification(self, source_id: i64) -> SourceVerification {
+        let now = chrono::Utc::now().naive_utc();
+        SourceVerification {
+            source_id,
+            ok: !self.is_corrupt(),
+            error: self.problem(),
+            duration: self.duration,
+            declared_duration: self.declared_duration,
+            packets: self.packets as i64,
+            lost_packets: self.lost_packets as i64,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/InitSongInfo.ts")]
 #[serde(rename_all = "camelCase")]
 pub enum InitSongInfo {
     Yt(YtInitSongInfo),
     Uploaded(UploadedInitSongInfo),
+    Url(UrlInitSongInfo),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/YtInitSongInfo.ts")]
 #[serde(rename_all = "camelCase")]
 pub struct YtInitSongInfo {
     pub url: Arc<str>,
+    /// Tag every song from a playlist or channel with its title
+    #[serde(default)]
+    #[ts(as = "Option<bool>", optional)]
+    pub playlist_as_tag: bool,
+    /// Changes to the configured yt-dlp options for this import
+    #[serde(default)]
+    #[ts(as = "Option<YtDlpOverrides>", optional)]
+    pub options: YtDlpOverrides,
+    /// Set once the url is expanded into single videos
+    #[serde(skip)]
+    pub video_id: Option<Arc<str>>,
+    /// Playlist or channel the video was expanded from
+    #[serde(skip)]
+    pub playlist_url: Option<Arc<str>>,
+    /// Tag to add to the song, from the playlist it was in
+    #[serde(skip)]
+    pub tag: Option<Arc<str>>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/UploadedInitSongInfo.ts")]
 #[serde(rename_all = "camelCase")]
 pub struct UploadedInitSongInfo {
     pub name: Arc<str>,
-    #[allow(unused)]
     pub size: usize,
     #[serde(rename = "type")]
     pub mime_type: Arc<str>,
+    /// Set when resuming an upload that was interrupted
+    #[serde(default)]
+    #[ts(optional)]
+    pub upload_id: Option<Arc<str>>,
+    /// Used instead of the configured templates for filling in missing tags
+    #[serde(default)]
+    #[ts(as = "Option<String>", optional)]
+    pub filename_template: Option<FilenameTemplate>,
+}
+
+/// A file downloaded straight from a http(s) url, with metadata for whatever its tags leave out
+#[derive(Debug, Clone, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/UrlInitSongInfo.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct UrlInitSongInfo {
+    pub url: Arc<str>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub title: Option<Arc<str>>,
+    #[serde(default)]
+    #[ts(optional)]
+    pub album: Option<Arc<str>>,
+    #[serde(default)]
+    #[ts(as = "Option<Vec<String>>", optional)]
+    pub artists: Vec<Arc<str>>,
+}
+
+/// Mime type of an audio file by its extension, only for types we accept
+pub fn mime_type_from_extension(extension: &str) -> Option<&'static str> {
+    Some(match &*extension.to_ascii_lowercase() {
+        "opus" | "ogg" | "oga" => "audio/ogg",
+        "m4a" | "mp4" | "aac" => "audio/mp4",
+        "mp3" => "audio/mpeg",
+        "flac" => "audio/flac",
+        _ => return None,
+    })
 }
 
 impl InitSongInfo {
+    /// Info for a song that's already in a storage backend, where only the mime type is known
+    pub fn stored(mime_type: &str) -> Self {
+        InitSongInfo::Uploaded(UploadedInitSongInfo {
+            name: Arc::from(""),
+            size: 0,
+            mime_type: Arc::from(mime_type),
+            upload_id: None,
+            filename_template: None,
+        })
+    }
+
     pub fn name(&self) -> Option<&str> {
         match self {
-            InitSongInfo::Yt(_) => None,
+            InitSongInfo::Yt(_) | InitSongInfo::Url(_) => None,
             InitSongInfo::Uploaded(uploaded_init_song_info) => {
                 Some(&*uploaded_init_song_info.name)
             }
         }
     }
 
+    /// What the item is called in messages, its file's name or url
+    pub fn display_name(&self) -> Arc<str> {
+        match self {
+            InitSongInfo::Yt(yt) => yt.url.clone(),
+            InitSongInfo::Url(url) => url.url.clone(),
+            InitSongInfo::Uploaded(uploaded) => uploaded.name.clone(),
+        }
+    }
+
+    pub fn filename_template(&self) -> Option<&FilenameTemplate> {
+        match self {
+            InitSongInfo::Yt(_) | InitSongInfo::Url(_) => None,
+            InitSongInfo::Uploaded(uploaded_init_song_info) => {
+                uploaded_init_song_info.filename_template.as_ref()
+            }
+        }
+    }
+
     pub fn mime_type(&self) -> Option<&str> {
         match self {
-            InitSongInfo::Yt(_) => None,
+            InitSongInfo::Yt(_) | InitSongInfo::Url(_) => None,
             InitSongInfo::Uploaded(uploaded_init_song_info) => {
                 Some(&*uploaded_init_song_info.mime_type)
             }
@@ -79,94 +248,116 @@ impl InitSongInfo {
     }
 }
 
-/// Returns the albums that had their cover populated
-pub async fn try_populate_album_covers(
+pub async fn get_source_verifications(
     extract::State(state): extract::State<State>,
     cookies: CookieJar,
-) -> Result<Json<Vec<String>>, ApiError> {
+) -> Result<Json<Vec<SourceVerification>>, ApiError> {
     let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
     if !user.admin {
         return Err(ApiError::Unauthorized);
     }
 
-    let albums = Album::get_all(&state.sqlite).await?;
-    let songs = Song::get_all_with_tags(&state.sqlite).await?;
-    let mut populated = Vec::with_capacity(albums.len());
+    Ok(Json(SourceVerification::get_all(&state.sqlite).await?))
+}
 
-    for album in albums {
-        // get song from op
-        let Some(song) = songs.iter().find(|s| s.tags.contains(&album.title)) else {
-            tracing::debug!("No song for album {}", album.title);
-            continue;
-        };
-        let Ok(sources) = Source::for_song(song.song.id, &state.sqlite).await else {
-            continue;
-        };
-        let Some(source) = sources.first().cloned() else {
-            tracing::debug!("song has no sources {}", song.song.title);
-            continue;
-        };
-        let Some(backend) =
-            StorageBackend::get_by_name(&source.storage_backend_name, &state.sqlite).await?
-        else {
-            continue;
-        };
-        let Ok(operator) = backend.operator().await else {
-            continue;
-        };
-        let song = operator.read(&source.path).await?;
-        let Ok(meta) = tokio::task::spawn_blocking(move || {
-            get_metadata(
-                song.to_bytes(),
-                &InitSongInfo::Uploaded(UploadedInitSongInfo {
-                    name: Arc::from(""),
-                    size: 0,
-                    mime_type: Arc::from(source.mime_type.as_str()),
-                }),
-            )
-        })
-        .await
-        .unwrap() else {
-            continue;
-        };
+/// Decodes the whole song to find corrupt packets, truncation and its real duration
+pub fn verify_audio(song: Box<dyn MediaSource>, mime_type: Option<&str>) -> DecodeReport {
+    let src = MediaSourceStream::new(song, Default::default());
+    let mut hint = Hint::new();
+    if let Some(mime_type) = mime_type {
+        hint.mime_type(mime_type);
+    }
 
-        let Some(album_cover) = meta.album_cover else {
-            tracing::debug!("No cover for {}", album.title);
-            continue;
+    let mut report = DecodeReport::default();
+    let mut probed = match symphonia::default::get_probe().format(
+        &hint,
+        src,
+        &Default::default(),
+        &Default::default(),
+    ) {
+        Ok(probed) => probed,
+        Err(err) => {
+            report.fatal_error = Some(format!("couldn't probe format: {err}"));
+            return report;
+        }
+    };
+
+    let Some(track) = probed
+        .format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+    else {
+        report.fatal_error = Some("no audio track".into());
+        return report;
+    };
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.unwrap_or_default();
+    report.declared_duration = track
+        .codec_params
+        .n_frames
+        .filter(|_| sample_rate != 0)
+        .map(|n_frames| n_frames as f64 / sample_rate as f64);
+
+    let mut decoder = match symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+    {
+        Ok(decoder) => decoder,
+        Err(err) => {
+            report.fatal_error = Some(format!("unsupported codec: {err}"));
+            return report;
+        }
+    };
+
+    let mut frames = 0u64;
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            // The normal way for a stream to end
+            Err(SymphoniaError::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break;
+            }
+            Err(err) => {
+                report.fatal_error = Some(format!("couldn't read packet: {err}"));
+                break;
+            }
         };
-        let cover_image_mime_type = &*album_cover.mime_type;
-        let cover_image_path = format!(
-            "images/{}.{}",
-            album.title.replace("/", "~slash~"),
-            cover_image_mime_type.split_once("/").unwrap().1
-        );
-        let write_image_res = operator.write(&cover_image_path, album_cover.data).await;
-        if let Err(err) = write_image_res {
-            tracing::error!("Error writing album cover to backend: {err:?}");
+
+        if packet.track_id() != track_id {
             continue;
-        } else {
-            let Ok(_) = Album::insert_w_source_and_tag(
-                &album.title,
-                &cover_image_path,
-                cover_image_mime_type,
-                &source.storage_backend_name,
-                &state.sqlite,
-            )
-            .await
-            else {
-                tracing::error!("Error writing new source to DB");
-                continue;
-            };
         }
 
-        populated.push(album.title);
+        report.packets += 1;
+        match decoder.decode(&packet) {
+            Ok(decoded) => frames += decoded.frames() as u64,
+            Err(err @ (SymphoniaError::DecodeError(_) | SymphoniaError::IoError(_))) => {
+                report.lost_packets += 1;
+                if report.errors.len() < MAX_REPORTED_DECODE_ERRORS {
+                    report.errors.push(format!("packet at {}: {err}", packet.ts()));
+                }
+            }
+            Err(err) => {
+                report.fatal_error = Some(format!("couldn't decode packet: {err}"));
+                break;
+            }
+        }
+    }
+
+    if sample_rate != 0 {
+        report.duration = frames as f64 / sample_rate as f64;
     }
 
-    Ok(Json(populated))
+    report
 }
 
-pub fn get_metadata(song: Bytes, info: &InitSongInfo) -> Result<ParsedMetadata, ApiError> {
-    let src = MediaSourceStream::new(Box::new(Cursor::new(song)), Default::default());
+/// Tags that are missing are filled in from the file's name with the first of `filename_templates`
+/// that matches it, or the template the song was sent with
+pub fn get_metadata(
+    song: Box<dyn MediaSource>,
+    info: &InitSongInfo,
+    filename_templates: &[FilenameTemplate],
+) -> Result<ParsedMetadata, ApiError> {
+    let src = MediaSourceStream::new(song, Default::default());
     let mut hint = Hint::new();
     if let Some(mime_type) = info.mime_type() {
         hint.mime_type(mime_type);
@@ -180,10 +371,14 @@ pub fn get_metadata(song: Bytes, info: &InitSongInfo) -> Result<ParsedMetadata,
     let mut probed = symphonia::default::get_probe().format(&hint, src, &fmt_opts, &meta_opts)?;
 
     let mut meta = ParsedMetadata {
-        title: info.name().map(Arc::from),
+        title: None,
         album: None,
         artists: vec![],
         album_cover: None,
+        album_artist: None,
+        track: None,
+        disc: None,
+        from_filename: None,
     };
 
     tracing::debug!("tracks: {:?}", probed.format.tracks());
@@ -193,12 +388,37 @@ pub fn get_metadata(song: Bytes, info: &InitSongInfo) -> Result<ParsedMetadata,
         populate_from_meta(&mut meta, metadata_rev);
     }
 
+    if let Some(name) = info.name().filter(|name| !name.is_empty()) {
+        if meta.title.is_none() || meta.album.is_none() || meta.artists.is_empty() {
+            let templates = info
+                .filename_template()
+                .map(std::slice::from_ref)
+                .unwrap_or(filename_templates);
+            meta.from_filename = FilenameTemplate::extract(templates, name);
+        }
+        if let Some(fields) = &meta.from_filename {
+            tracing::debug!("Found {fields:?} in file name {name:?}");
+            meta.title = meta.title.or_else(|| fields.title.clone());
+            meta.album = meta.album.or_else(|| fields.album.clone());
+            meta.track = meta.track.or(fields.track);
+            if meta.artists.is_empty() {
+                meta.artists.extend(fields.artist.clone());
+            }
+        }
+        // Title falls back to the whole name
+        meta.title = meta.title.or_else(|| Some(Arc::from(name)));
+    }
+
     Ok(meta)
 }
 
 pub fn populate_from_meta(meta: &mut ParsedMetadata, parsed_meta: &MetadataRevision) {
     meta.title = get_string_tag(parsed_meta.tags(), StandardTagKey::TrackTitle).map(Arc::from);
     meta.album = get_string_tag(parsed_meta.tags(), StandardTagKey::Album).map(Arc::from);
+    meta.album_artist =
+        get_string_tag(parsed_meta.tags(), StandardTagKey::AlbumArtist).map(Arc::from);
+    meta.track = get_number_tag(parsed_meta.tags(), StandardTagKey::TrackNumber);
+    meta.disc = get_number_tag(parsed_meta.tags(), StandardTagKey::DiscNumber);
     meta.album_cover = parsed_meta
         .visuals()
         .iter()
@@ -220,6 +440,20 @@ pub fn populate_from_meta(meta: &mut ParsedMetadata, parsed_meta: &MetadataRevis
     }
 }
 
+/// Numbers like track numbers can be stored as `3` or `3/12`
+pub fn get_number_tag(tags: &[Tag], key: StandardTagKey) -> Option<u32> {
+    tags.iter()
+        .find(|t| t.std_key.is_some_and(|k| k == key))
+        .and_then(|t| match &t.value {
+            symphonia::core::meta::Value::UnsignedInt(v) => u32::try_from(*v).ok(),
+            symphonia::core::meta::Value::SignedInt(v) => u32::try_from(*v).ok(),
+            symphonia::core::meta::Value::String(v) => {
+                v.split('/').next().and_then(|n| n.trim().parse().ok())
+            }
+            _ => None,
+        })
+}
+
 pub fn get_string_tag(tags: &[Tag], key: StandardTagKey) -> Option<&str> {
     tags.iter()
         .find(|t| t.std_key.is_some_and(|k| k == key))
@@ -1,9 +1,9 @@
 use axum::{extract, Json};
 use axum_extra::extract::CookieJar;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    db::{song::SongWTags, source::{AlbumSource, SongSource}, Song, Source, Tag, User},
+    db::{provenance::SongProvenance, song::SongWTags, source::{AlbumSource, SongSource}, Song, Source, Tag, User},
     ApiError,
 };
 
@@ -12,12 +12,54 @@ use super::{
     State,
 };
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongsQuery {
+    /// Songs waiting in the review inbox are left out unless an admin sets this
+    #[serde(default)]
+    include_pending: bool,
+}
+
 pub async fn get_songs(
     extract::State(state): extract::State<State>,
+    extract::Query(query): extract::Query<SongsQuery>,
     cookies: CookieJar,
 ) -> Result<Json<Vec<SongWTags>>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if query.include_pending && !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(Json(
+        Song::get_all_with_tags(query.include_pending, &state.sqlite).await?,
+    ))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongDetails {
+    #[serde(flatten)]
+    song: Song,
+    tags: Vec<Tag>,
+    /// Missing for songs added before provenance was recorded
+    provenance: Option<SongProvenance>,
+}
+
+pub async fn get_song(
+    extract::Path(song_id): extract::Path<i64>,
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<SongDetails>, ApiError> {
     let _user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
-    Ok(Json(Song::get_all_with_tags(&state.sqlite).await?))
+    let Some(song) = Song::get_by_id(song_id, &state.sqlite).await? else {
+        return Err(ApiError::NotFound);
+    };
+
+    Ok(Json(SongDetails {
+        song,
+        tags: Tag::for_song(song_id, &state.sqlite).await?,
+        provenance: SongProvenance::get_by_song_id(song_id, &state.sqlite).await?,
+    }))
 }
 
 pub async fn get_sources_for_song(
@@ -53,6 +95,44 @@ pub async fn get_all_sources_for_albums(
     Ok(Json(Source::get_all_for_albums(&state.sqlite).await?))
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SongMetadata {
+    pub title: String,
+    pub album: Option<String>,
+    #[serde(default)]
+    pub artists: Vec<String>,
+}
+
+/// Manually edit a song's metadata, which also protects it from re-scans
+pub async fn update_song(
+    extract::Path(song_id): extract::Path<i64>,
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+    Json(meta): Json<SongMetadata>,
+) -> Result<(), ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    if Song::get_by_id(song_id, &state.sqlite).await?.is_none() {
+        return Err(ApiError::NotFound);
+    }
+
+    let artists = meta.artists.iter().map(String::as_str).collect::<Vec<_>>();
+    Song::set_metadata(
+        song_id,
+        &meta.title,
+        meta.album.as_deref(),
+        &artists,
+        true,
+        &state.sqlite,
+    )
+    .await?;
+    Ok(())
+}
+
 pub async fn delete_song(
     extract::Path(song_id): extract::Path<i64>,
     extract::State(state): extract::State<State>,
@@ -63,7 +143,7 @@ pub async fn delete_song(
         return Err(ApiError::Unauthorized);
     }
 
-    Song::delete_w_sources(song_id, &state.sqlite).await?;
+    Song::delete(song_id, &state.sqlite).await?;
     Ok(())
 }
 
@@ -0,0 +1,193 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::{Json, extract};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ApiError,
+    api::{
+        add_song::{
+            ALLOWED_MIME_TYPES, AddSongResult, SongFile, add_song, default_storage_backend_name,
+            final_metadata, probe,
+        },
+        audio::{InitSongInfo, UploadedInitSongInfo},
+        media_source::ReaderMediaSource,
+        upload::PresignedUpload,
+    },
+    db::{
+        StorageBackend,
+        provenance::{ImportMethod, SongProvenance},
+    },
+};
+
+use super::{
+    State,
+    auth::{AUTH_COOKIE, authenticate},
+    filename_template::FilenameTemplate,
+};
+
+/// How long the client has to start uploading after asking for a presigned request
+const PRESIGNED_UPLOAD_VALID_FOR: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignUploadRequest {
+    name: Arc<str>,
+    #[serde(rename = "type")]
+    mime_type: Arc<str>,
+    #[serde(default = "default_storage_backend_name")]
+    storage_backend: Arc<str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignedUploadResponse {
+    upload_id: Arc<str>,
+    request: UploadRequest,
+}
+
+/// Request the client sends the file with, straight to the storage backend
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadRequest {
+    #[serde(with = "http_serde::method")]
+    method: http::Method,
+    #[serde(with = "http_serde::uri")]
+    uri: http::Uri,
+    #[serde(with = "http_serde::header_map")]
+    headers: http::HeaderMap,
+}
+
+/// Final metadata for a presigned upload, anything left out is taken from the file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CompleteUploadRequest {
+    title: Option<Arc<str>>,
+    album: Option<Arc<str>>,
+    artists: Vec<Arc<str>>,
+    /// Used instead of the configured templates for filling in missing tags
+    filename_template: Option<FilenameTemplate>,
+}
+
+/// 1. Client asks for a presigned request to a staging key here
+/// 2. Client uploads the file straight to the storage backend
+/// 3. Client completes the upload with [`complete_upload`]
+pub async fn presign_upload(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+    extract::Json(req): extract::Json<PresignUploadRequest>,
+) -> Result<Json<PresignedUploadResponse>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    if !ALLOWED_MIME_TYPES.contains(&&*req.mime_type) {
+        return Err(ApiError::BadRequest(format!(
+            "Invalid mime type: {}",
+            req.mime_type
+        )));
+    }
+
+    let operator = StorageBackend::operator_by_name(&req.storage_backend, &state.sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !operator.info().full_capability().presign_write {
+        return Err(ApiError::BadRequest(format!(
+            "Storage backend {} doesn't support presigned uploads",
+            req.storage_backend
+        )));
+    }
+
+    let upload = PresignedUpload::new(req.storage_backend, req.name, req.mime_type);
+    let presigned = operator
+        .presign_write_with(&upload.key, PRESIGNED_UPLOAD_VALID_FOR)
+        .content_type(&upload.mime_type)
+        .await?;
+    upload.save(&state.config.data_dir).await?;
+
+    Ok(Json(PresignedUploadResponse {
+        upload_id: upload.id.clone(),
+        request: UploadRequest {
+            method: presigned.method().clone(),
+            uri: presigned.uri().clone(),
+            headers: presigned.header().clone(),
+        },
+    }))
+}
+
+/// Probes the staged object, then moves it to its final path and adds the song.
+/// If adding fails for a reason other than the file being corrupt, the client can try again.
+pub async fn complete_upload(
+    extract::State(state): extract::State<State>,
+    extract::Path(upload_id): extract::Path<String>,
+    cookies: CookieJar,
+    extract::Json(req): extract::Json<CompleteUploadRequest>,
+) -> Result<Json<AddSongResult>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let data_dir = &state.config.data_dir;
+    let Some(upload) = PresignedUpload::load(data_dir, &upload_id).await? else {
+        return Err(ApiError::NotFound);
+    };
+    let operator = StorageBackend::operator_by_name(&upload.storage_backend, &state.sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    let info = Arc::new(InitSongInfo::Uploaded(UploadedInitSongInfo {
+        name: upload.name.clone(),
+        size: operator.stat(&upload.key).await?.content_length() as usize,
+        mime_type: upload.mime_type.clone(),
+        upload_id: Some(upload.id.clone()),
+        filename_template: req.filename_template,
+    }));
+    let (parsed_meta, decode_report) = probe(
+        Box::new(ReaderMediaSource::new(&operator, &upload.key).await?),
+        Box::new(ReaderMediaSource::new(&operator, &upload.key).await?),
+        info,
+        upload.mime_type.clone(),
+        state.config.filename_templates.clone(),
+    )
+    .await?;
+    if let Some(problem) = decode_report.problem() {
+        if let Err(err) = operator.delete(&upload.key).await {
+            tracing::error!("Couldn't delete corrupt upload {}: {err:?}", upload.key);
+        }
+        upload.remove(data_dir).await;
+        return Err(ApiError::BadRequest(format!(
+            "Corrupt audio file: {problem}"
+        )));
+    }
+
+    let final_meta = final_metadata(
+        req.title,
+        req.album,
+        req.artists,
+        upload.storage_backend.clone(),
+        &parsed_meta,
+    )?;
+    tracing::debug!("Completing presigned upload {}: {final_meta:#?}", upload.id);
+
+    let res = add_song(
+        &state.sqlite,
+        SongFile::InBackend {
+            key: &upload.key,
+            mime_type: &upload.mime_type,
+        },
+        final_meta,
+        parsed_meta.album_cover,
+        &SongProvenance::new(
+            ImportMethod::Presigned,
+            Some(&user.username),
+            Some(&upload.name),
+        ),
+    )
+    .await?;
+    upload.remove(data_dir).await;
+
+    Ok(Json(res))
+}
@@ -0,0 +1,139 @@
+use axum::{Json, extract};
+use axum_extra::extract::CookieJar;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ApiError,
+    db::{Song, fingerprint::SongFingerprint},
+    fingerprint::{DEFAULT_THRESHOLD, Fingerprint, group_similar},
+};
+
+use super::{
+    State,
+    auth::{AUTH_COOKIE, authenticate},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicatesQuery {
+    /// How alike fingerprints have to be, unrelated songs are around 0.5
+    #[serde(default = "default_threshold")]
+    threshold: f32,
+}
+
+fn default_threshold() -> f32 {
+    DEFAULT_THRESHOLD
+}
+
+/// Songs that sound the same, found by their fingerprints
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/DuplicateGroup.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateGroup {
+    /// Lowest similarity between two songs that put them in this group
+    pub similarity: f32,
+    pub songs: Vec<DuplicateSong>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/DuplicateSong.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateSong {
+    #[ts(type = "number")]
+    pub song_id: i64,
+    pub title: String,
+    /// Seconds
+    pub duration: f64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeSongs {
+    /// Songs whose sources are moved to the song being merged into, they're deleted after
+    song_ids: Vec<i64>,
+}
+
+/// Groups songs that have similar fingerprints and durations. Songs are only fingerprinted by
+/// the `fingerprintSongs` job.
+pub async fn get_duplicates(
+    extract::State(state): extract::State<State>,
+    extract::Query(query): extract::Query<DuplicatesQuery>,
+    cookies: CookieJar,
+) -> Result<Json<Vec<DuplicateGroup>>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let (song_ids, fingerprints): (Vec<_>, Vec<_>) = SongFingerprint::get_all(&state.sqlite)
+        .await?
+        .into_iter()
+        .map(|f| {
+            (
+                f.song_id,
+                Fingerprint {
+                    hashes: f.hashes(),
+                    duration: f.duration,
+                },
+            )
+        })
+        .unzip();
+    let (groups, fingerprints) = tokio::task::spawn_blocking(move || {
+        (group_similar(&fingerprints, query.threshold), fingerprints)
+    })
+    .await
+    .unwrap();
+
+    let titles = Song::get_all(&state.sqlite)
+        .await?
+        .into_iter()
+        .map(|song| (song.id, song.title))
+        .collect::<FxHashMap<_, _>>();
+
+    Ok(Json(
+        groups
+            .into_iter()
+            .map(|(group, similarity)| DuplicateGroup {
+                similarity,
+                songs: group
+                    .into_iter()
+                    .map(|i| DuplicateSong {
+                        song_id: song_ids[i],
+                        title: titles.get(&song_ids[i]).cloned().unwrap_or_default(),
+                        duration: fingerprints[i].duration,
+                    })
+                    .collect(),
+            })
+            .collect(),
+    ))
+}
+
+/// Merges the given songs into this one, which keeps its metadata and gets all their sources
+pub async fn merge_songs(
+    extract::State(state): extract::State<State>,
+    extract::Path(song_id): extract::Path<i64>,
+    cookies: CookieJar,
+    Json(merge): Json<MergeSongs>,
+) -> Result<Json<Song>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    if merge.song_ids.contains(&song_id) {
+        return Err(ApiError::BadRequest(
+            "Can't merge a song into itself".to_string(),
+        ));
+    }
+    for id in merge.song_ids.iter().chain([&song_id]) {
+        if Song::get_by_id(*id, &state.sqlite).await?.is_none() {
+            return Err(ApiError::NotFound);
+        }
+    }
+
+    Song::merge(song_id, &merge.song_ids, &state.sqlite).await?;
+    Song::get_by_id(song_id, &state.sqlite)
+        .await?
+        .map(Json)
+        .ok_or(ApiError::NotFound)
+}
@@ -0,0 +1,206 @@
+use std::{path::Path, str::FromStr, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use super::audio::mime_type_from_extension;
+
+/// Pattern to pull metadata out of untagged files' names, like `{track} - {artist} - {title}`.
+/// `{_}` matches anything and is thrown away.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct FilenameTemplate {
+    template: Arc<str>,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Track,
+    Title,
+    Album,
+    Artist,
+    Ignore,
+}
+
+/// What a template found in a file's name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/FilenameFields.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct FilenameFields {
+    /// The template that matched
+    pub template: Arc<str>,
+    pub track: Option<u32>,
+    pub title: Option<Arc<str>>,
+    pub album: Option<Arc<str>>,
+    pub artist: Option<Arc<str>>,
+}
+
+pub fn default_filename_templates() -> Arc<[FilenameTemplate]> {
+    [
+        "{track} - {artist} - {title}",
+        "{track} - {title}",
+        "{track}. {title}",
+        "{artist} - {title}",
+    ]
+    .into_iter()
+    .map(|template| template.parse().unwrap())
+    .collect()
+}
+
+impl FilenameTemplate {
+    /// Fields from the first template that matches the name, extensions of audio files are ignored
+    pub fn extract(templates: &[FilenameTemplate], name: &str) -> Option<FilenameFields> {
+        let path = Path::new(name);
+        let name = match (path.file_stem(), path.extension()) {
+            (Some(stem), Some(ext))
+                if mime_type_from_extension(&ext.to_string_lossy()).is_some() =>
+            {
+                stem.to_string_lossy()
+            }
+            _ => name.into(),
+        };
+
+        templates
+            .iter()
+            .find_map(|template| template.matches(name.trim()))
+    }
+
+    fn matches(&self, name: &str) -> Option<FilenameFields> {
+        let mut found = Vec::new();
+        if !match_segments(&self.segments, name, &mut found) {
+            return None;
+        }
+
+        let mut fields = FilenameFields {
+            template: self.template.clone(),
+            ..Default::default()
+        };
+        for (field, value) in found {
+            let value = value.trim();
+            match field {
+                Field::Track => fields.track = value.parse().ok(),
+                Field::Title => fields.title = Some(Arc::from(value)),
+                Field::Album => fields.album = Some(Arc::from(value)),
+                Field::Artist => fields.artist = Some(Arc::from(value)),
+                Field::Ignore => {}
+            }
+        }
+        Some(fields)
+    }
+}
+
+/// Fields take as little as they can so the literals after them match as early as possible,
+/// backtracking if the rest of the name doesn't match
+fn match_segments<'a>(
+    segments: &[Segment],
+    name: &'a str,
+    found: &mut Vec<(Field, &'a str)>,
+) -> bool {
+    let Some((segment, rest)) = segments.split_first() else {
+        return name.is_empty();
+    };
+
+    match segment {
+        Segment::Literal(literal) => name
+            .strip_prefix(&**literal)
+            .is_some_and(|name| match_segments(rest, name, found)),
+        Segment::Field(field) => {
+            let ends = name
+                .char_indices()
+                .skip(1)
+                .map(|(i, _)| i)
+                .chain([name.len()]);
+            for end in ends {
+                let value = &name[..end];
+                if !field.accepts(value) {
+                    // Tracks can't become valid again by taking more
+                    if *field == Field::Track {
+                        return false;
+                    }
+                    continue;
+                }
+
+                found.push((*field, value));
+                if match_segments(rest, &name[end..], found) {
+                    return true;
+                }
+                found.pop();
+            }
+            false
+        }
+    }
+}
+
+impl Field {
+    fn accepts(self, value: &str) -> bool {
+        match self {
+            Field::Track => value.bytes().all(|b| b.is_ascii_digit()),
+            _ => !value.trim().is_empty(),
+        }
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                segments.push(Segment::Literal(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let Some(len) = rest[start..].find('}') else {
+                return Err(format!("Unclosed {{ in filename template {template:?}"));
+            };
+            let field = match &rest[start + 1..start + len] {
+                "track" => Field::Track,
+                "title" => Field::Title,
+                "album" => Field::Album,
+                "artist" => Field::Artist,
+                "_" => Field::Ignore,
+                name => {
+                    return Err(format!(
+                        "Unknown field {{{name}}} in filename template {template:?}"
+                    ));
+                }
+            };
+            if let Some(Segment::Field(_)) = segments.last() {
+                return Err(format!(
+                    "Fields need something between them in filename template {template:?}"
+                ));
+            }
+            segments.push(Segment::Field(field));
+            rest = &rest[start + len + 1..];
+        }
+
+        if !segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Field(_)))
+        {
+            return Err(format!("No fields in filename template {template:?}"));
+        }
+        Ok(Self {
+            template: Arc::from(template),
+            segments,
+        })
+    }
+}
+
+impl TryFrom<String> for FilenameTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        template.parse()
+    }
+}
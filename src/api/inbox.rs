@@ -0,0 +1,152 @@
+use axum::{Json, extract};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ApiError,
+    db::{
+        Song, Tag, metadata_diff::MetadataDiff, provenance::SongProvenance, review::PendingReview,
+    },
+};
+
+use super::{
+    State,
+    auth::{AUTH_COOKIE, authenticate},
+    crud::SongMetadata,
+    filename_template::{FilenameFields, FilenameTemplate},
+};
+
+/// A song waiting for review, with what it was imported with and what else we could use
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/InboxSong.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct InboxSong {
+    #[serde(flatten)]
+    song: Song,
+    /// Current album and artists, which change if the song is edited while it's pending
+    album: Option<String>,
+    artists: Vec<String>,
+    /// Parsed from the file, unless the import sent its own metadata
+    imported: PendingReview,
+    provenance: Option<SongProvenance>,
+    /// What a filename template finds in the name the song was imported with
+    from_filename: Option<FilenameFields>,
+    /// What a re-scan found in the file, if it differs
+    rescan: Option<MetadataDiff>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApproveSongs {
+    /// Approve every pending song when not given
+    #[serde(default)]
+    song_ids: Option<Vec<i64>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApproveResult {
+    approved: Vec<i64>,
+    /// Songs that don't exist or were already approved
+    not_pending: Vec<i64>,
+}
+
+pub async fn get_inbox(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<Vec<InboxSong>>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let mut inbox = Vec::new();
+    for imported in PendingReview::get_all(&state.sqlite).await? {
+        let Some(song) = Song::get_by_id(imported.song_id, &state.sqlite).await? else {
+            continue;
+        };
+        let tags = Tag::for_song(song.id, &state.sqlite).await?;
+        let (album, artists) = Tag::album_and_artists(&tags);
+        let provenance = SongProvenance::get_by_song_id(song.id, &state.sqlite).await?;
+        let from_filename = provenance
+            .as_ref()
+            .and_then(|provenance| provenance.original_name.as_deref())
+            .and_then(|name| FilenameTemplate::extract(&state.config.filename_templates, name));
+        let rescan = MetadataDiff::get_by_song_id(song.id, &state.sqlite).await?;
+
+        inbox.push(InboxSong {
+            song,
+            album,
+            artists,
+            imported,
+            provenance,
+            from_filename,
+            rescan,
+        });
+    }
+
+    Ok(Json(inbox))
+}
+
+/// Lets songs into the library with the metadata they have
+pub async fn approve_songs(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+    Json(approve): Json<ApproveSongs>,
+) -> Result<Json<ApproveResult>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let song_ids = match approve.song_ids {
+        Some(song_ids) => song_ids,
+        None => PendingReview::get_all(&state.sqlite)
+            .await?
+            .into_iter()
+            .map(|review| review.song_id)
+            .collect(),
+    };
+
+    let mut res = ApproveResult::default();
+    for song_id in song_ids {
+        if PendingReview::approve(song_id, &state.sqlite).await? {
+            res.approved.push(song_id);
+        } else {
+            res.not_pending.push(song_id);
+        }
+    }
+
+    Ok(Json(res))
+}
+
+/// Edits a pending song's metadata like [`super::crud::update_song`] and approves it
+pub async fn edit_and_approve_song(
+    extract::Path(song_id): extract::Path<i64>,
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+    Json(meta): Json<SongMetadata>,
+) -> Result<(), ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    match Song::get_by_id(song_id, &state.sqlite).await? {
+        Some(song) if song.pending_review => {}
+        _ => return Err(ApiError::NotFound),
+    }
+
+    let artists = meta.artists.iter().map(String::as_str).collect::<Vec<_>>();
+    Song::set_metadata(
+        song_id,
+        &meta.title,
+        meta.album.as_deref(),
+        &artists,
+        true,
+        &state.sqlite,
+    )
+    .await?;
+    PendingReview::approve(song_id, &state.sqlite).await?;
+    Ok(())
+}
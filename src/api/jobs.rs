@@ -0,0 +1,137 @@
+use axum::{Json, extract};
+use axum_extra::extract::CookieJar;
+use serde::Serialize;
+
+use crate::{
+    ApiError,
+    db::{
+        job::{Job, JobLog, JobResult},
+        scheduled_task::ScheduledTask,
+    },
+    jobs::JobSpec,
+};
+
+use super::{
+    State,
+    auth::{AUTH_COOKIE, authenticate},
+};
+
+/// How many of the latest watch folder results to send
+const WATCH_FOLDER_RESULTS_LIMIT: i64 = 500;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobDetails {
+    #[serde(flatten)]
+    job: Job,
+    logs: Vec<JobLog>,
+    results: Vec<JobResult>,
+}
+
+pub async fn start_job(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+    Json(spec): Json<JobSpec>,
+) -> Result<Json<Job>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let id = state.jobs.start(spec, Some(&user.username)).await?;
+    Ok(Json(
+        Job::get_by_id(id, &state.sqlite)
+            .await?
+            .ok_or(ApiError::NotFound)?,
+    ))
+}
+
+/// Kept for scripts from before jobs, starts a `populateAlbumCovers` job
+pub async fn populate_album_covers(
+    state: extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<Job>, ApiError> {
+    start_job(state, cookies, Json(JobSpec::PopulateAlbumCovers)).await
+}
+
+pub async fn get_jobs(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<Vec<Job>>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(Json(Job::get_all(&state.sqlite).await?))
+}
+
+pub async fn get_job(
+    extract::Path(job_id): extract::Path<i64>,
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<JobDetails>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let job = Job::get_by_id(job_id, &state.sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+
+    Ok(Json(JobDetails {
+        job,
+        logs: JobLog::for_job(job_id, &state.sqlite).await?,
+        results: JobResult::for_job(job_id, &state.sqlite).await?,
+    }))
+}
+
+pub async fn cancel_job(
+    extract::Path(job_id): extract::Path<i64>,
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<(), ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    if !state.jobs.cancel(job_id) {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(())
+}
+
+/// What happened to the files dropped into the watch folder, newest first
+pub async fn get_watch_folder_results(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<Vec<JobResult>>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(Json(
+        JobResult::latest_for_kind(
+            JobSpec::IngestWatchFolder(Default::default()).kind(),
+            WATCH_FOLDER_RESULTS_LIMIT,
+            &state.sqlite,
+        )
+        .await?,
+    ))
+}
+
+pub async fn get_schedules(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<Vec<ScheduledTask>>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(Json(ScheduledTask::get_all(&state.sqlite).await?))
+}
@@ -0,0 +1,99 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use axum::body::Bytes;
+use opendal::{Operator, Reader};
+use symphonia::core::io::MediaSource;
+use tokio::runtime::Handle;
+
+/// How much to fetch from the backend each time we run out of buffered bytes
+const READ_AHEAD: u64 = 256 * 1024;
+
+/// A [`MediaSource`] over an object in a storage backend that only fetches the byte ranges
+/// symphonia actually reads, instead of loading the whole file into memory.
+///
+/// Reads block on the runtime, so this must only be used from blocking threads (i.e. `spawn_blocking`).
+pub struct ReaderMediaSource {
+    reader: Reader,
+    handle: Handle,
+    len: u64,
+    pos: u64,
+    buf: Bytes,
+    buf_start: u64,
+}
+
+impl ReaderMediaSource {
+    pub async fn new(operator: &Operator, path: &str) -> Result<Self, opendal::Error> {
+        let len = operator.stat(path).await?.content_length();
+        let reader = operator.reader(path).await?;
+
+        Ok(Self {
+            reader,
+            handle: Handle::current(),
+            len,
+            pos: 0,
+            buf: Bytes::new(),
+            buf_start: 0,
+        })
+    }
+}
+
+impl Read for ReaderMediaSource {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+
+        let buf_end = self.buf_start + self.buf.len() as u64;
+        if self.pos < self.buf_start || self.pos >= buf_end {
+            // Outside of what we have buffered, fetch the next chunk from the backend
+            let end = (self.pos + READ_AHEAD.max(out.len() as u64)).min(self.len);
+            self.buf = self
+                .handle
+                .block_on(self.reader.read(self.pos..end))
+                .map_err(io::Error::other)?
+                .to_bytes();
+            self.buf_start = self.pos;
+
+            if self.buf.is_empty() {
+                return Ok(0);
+            }
+        }
+
+        let offset = (self.pos - self.buf_start) as usize;
+        let n = out.len().min(self.buf.len() - offset);
+        out[..n].copy_from_slice(&self.buf[offset..offset + n]);
+        self.pos += n as u64;
+
+        Ok(n)
+    }
+}
+
+impl Seek for ReaderMediaSource {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        let Some(new_pos) = new_pos else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
+
+impl MediaSource for ReaderMediaSource {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
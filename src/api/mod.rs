@@ -1,7 +1,18 @@
 pub mod add_song;
+mod archive;
 mod auth;
 mod crud;
 pub mod audio;
+mod direct_upload;
+mod duplicates;
+mod inbox;
+pub mod filename_template;
+mod jobs;
+pub mod media_source;
+pub mod protocol;
+mod replace_audio;
+mod rescan;
+pub mod upload;
 
 use std::{
     ops::{Bound, RangeBounds},
@@ -12,9 +23,9 @@ use std::{
 use auth::{authenticate, AUTH_COOKIE};
 use axum::{
     body::Body,
-    extract,
+    extract::{self, DefaultBodyLimit},
     response::Response,
-    routing::{delete, get, post},
+    routing::{get, post, put},
     Router,
 };
 use axum_extra::extract::CookieJar;
@@ -25,30 +36,79 @@ use http::{
 };
 use sqlx::{Pool, Sqlite};
 
-use crate::{config::Config, db::Source, ApiError};
+use crate::{
+    config::Config, db::Source, importers::Importers, jobs::Jobs, yt_dlp::YtDlp, ApiError,
+};
 
 #[derive(Debug, Clone)]
 pub struct State {
     config: Arc<Config>,
     sqlite: Pool<Sqlite>,
+    jobs: Jobs,
+    yt_dlp: YtDlp,
+    importers: Importers,
 }
 
-pub fn api_router(config: Arc<Config>, sqlite: Pool<Sqlite>) -> color_eyre::Result<Router> {
-    let state = State { config, sqlite };
+pub fn api_router(
+    config: Arc<Config>,
+    sqlite: Pool<Sqlite>,
+    jobs: Jobs,
+    yt_dlp: YtDlp,
+) -> color_eyre::Result<Router> {
+    let state = State {
+        importers: Importers::new(config.clone(), yt_dlp.clone()),
+        config,
+        sqlite,
+        jobs,
+        yt_dlp,
+    };
     let router = Router::new()
         .route("/login", post(auth::login))
         .route("/check-auth", get(auth::check_auth))
         .route("/tags", get(crud::get_tags))
         .route("/add-songs", get(add_song::handler))
-        .route("/songs", get(crud::get_songs))
-        .route("/songs/{id}", delete(crud::delete_song))
+        .route(
+            "/songs",
+            get(crud::get_songs)
+                .post(add_song::upload_song)
+                .layer(DefaultBodyLimit::max(upload::MAX_UPLOAD_SIZE)),
+        )
+        .route(
+            "/songs/{id}",
+            get(crud::get_song)
+                .put(crud::update_song)
+                .delete(crud::delete_song),
+        )
+        .route("/uploads/presign", post(direct_upload::presign_upload))
+        .route(
+            "/uploads/{id}/complete",
+            post(direct_upload::complete_upload),
+        )
         .route("/songs/{id}/sources", get(crud::get_sources_for_song))
+        .route("/songs/{id}/merge", post(duplicates::merge_songs))
+        .route(
+            "/songs/{id}/audio",
+            post(replace_audio::replace_song_audio)
+                .layer(DefaultBodyLimit::max(upload::MAX_UPLOAD_SIZE)),
+        )
+        .route("/songs/duplicates", get(duplicates::get_duplicates))
         .route("/songs/sources", get(crud::get_all_sources_for_songs))
         .route("/albums/sources", get(crud::get_all_sources_for_albums))
-        .route("/albums/populate-covers", get(audio::try_populate_album_covers))
+        .route("/albums/populate-covers", get(jobs::populate_album_covers))
         .route("/sources", get(crud::get_sources))
+        .route("/sources/verifications", get(audio::get_source_verifications))
         .route("/sources/{id}/data", get(get_source))
         .route("/users", get(crud::get_users).post(crud::create_user))
+        .route("/jobs", get(jobs::get_jobs).post(jobs::start_job))
+        .route("/jobs/{id}", get(jobs::get_job))
+        .route("/jobs/{id}/cancel", post(jobs::cancel_job))
+        .route("/schedules", get(jobs::get_schedules))
+        .route("/watch-folder", get(jobs::get_watch_folder_results))
+        .route("/rescan/diffs", get(rescan::get_metadata_diffs))
+        .route("/rescan/diffs/apply", post(rescan::apply_metadata_diffs))
+        .route("/inbox", get(inbox::get_inbox))
+        .route("/inbox/approve", post(inbox::approve_songs))
+        .route("/inbox/{id}", put(inbox::edit_and_approve_song))
         .with_state(state);
 
     Ok(router)
@@ -0,0 +1,337 @@
+//! Messages of the add songs WS. The client starts with a [`ClientMessage::Hello`] with the
+//! version it speaks, after that every text message either way is one of these. Song files
+//! are sent as binary messages, see [`ServerMessage::Upload`].
+
+use std::sync::Arc;
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket, close_code};
+use serde::{Deserialize, Serialize};
+
+use crate::ApiError;
+
+use super::{
+    add_song::{AddSongFailure, AddSongResult, FinalMetadata},
+    audio::{DecodeReport, InitSongInfo},
+    filename_template::FilenameFields,
+    replace_audio::{ReplaceAudio, ReplaceAudioResult},
+};
+
+/// Bumped whenever a message changes in a way old clients can't handle
+///
+/// 1. The first versioned protocol. Compared to the unversioned one before it, songs can also
+///    be [`InitSongInfo::Url`], and items fail on their own instead of ending the WS
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Everything the client sends as text
+#[derive(Debug, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ClientMessage.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum ClientMessage {
+    /// Has to be the first message
+    Hello(Hello),
+    /// The songs to import, playlists and channels are expanded by the server
+    Songs(Box<[Arc<InitSongInfo>]>),
+    /// Final metadata for the song we sent [`ServerMessage::Metadata`] for
+    Metadata(FinalMetadata),
+    /// Final metadata for every track of the album we sent [`ServerMessage::Archive`] for
+    Album(FinalAlbumMetadata),
+    /// Answers [`ServerMessage::ItemFailed`], or skips the item instead of sending its metadata
+    Decision(Decision),
+    /// Sent instead of final metadata to make the file the new audio of a song we already have
+    Replace(ReplaceAudio),
+}
+
+/// Everything the server sends
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ServerMessage.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum ServerMessage {
+    /// Answers the client's hello if we speak its version
+    Hello(Hello),
+    /// Sent once we know which songs will be imported
+    Batch(BatchInfo),
+    /// How much of an uploaded file we have, the client sends the rest in binary messages of
+    /// an 8 byte big endian offset followed by the chunk
+    Upload(UploadStatus),
+    /// Sent while a song downloads in the background, null while it's queued
+    Progress(Option<f32>),
+    /// The file is the same as a song we already have, the client answers with a
+    /// [`ClientMessage::Decision`] to link it or skip it
+    Duplicate(DuplicateInfo),
+    /// What we found in a song's file
+    Metadata(SongMetadata),
+    /// Sent instead of [`ServerMessage::Metadata`] for an album uploaded as an archive
+    Archive(ArchiveInfo),
+    /// A song was added
+    Added(AddSongResult),
+    /// A song's audio was replaced
+    Replaced(ReplaceAudioResult),
+    /// Every track of an archive was added, corrupt tracks are left out
+    Songs(Vec<AddSongResult>),
+    ItemFailed(ItemFailure),
+    /// Sent after the last item of the batch
+    Summary(SummaryInfo),
+    Error(ErrorInfo),
+}
+
+#[derive(Debug, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/Hello.ts")]
+pub struct Hello {
+    pub version: u32,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/BatchInfo.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct BatchInfo {
+    /// How many songs the client should expect to go through
+    pub songs: usize,
+    /// Urls of videos that were already imported
+    pub skipped: Vec<Arc<str>>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/UploadStatus.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct UploadStatus {
+    /// Send it again to resume the upload if the connection drops
+    pub upload_id: Arc<str>,
+    #[ts(type = "number")]
+    pub offset: u64,
+    #[ts(type = "number")]
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/DuplicateInfo.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateInfo {
+    /// The song that has the same file
+    #[ts(type = "number")]
+    pub song_id: i64,
+    pub title: String,
+    pub content_hash: String,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/SongMetadata.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct SongMetadata {
+    pub title: Option<Arc<str>>,
+    pub album: Option<Arc<str>>,
+    pub artists: Vec<Arc<str>>,
+    pub decode_report: DecodeReport,
+    /// What a filename template found, if the tags were missing something
+    pub from_filename: Option<FilenameFields>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ArchiveInfo.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveInfo {
+    /// First album and artists found in the tracks' tags
+    pub album: Option<Arc<str>>,
+    pub artists: Vec<Arc<str>>,
+    /// Whether there was a cover.jpg or folder.jpg in the archive
+    pub has_cover: bool,
+    pub tracks: Vec<TrackMetadata>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/TrackMetadata.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct TrackMetadata {
+    /// Path of the track in the archive
+    pub name: Arc<str>,
+    #[serde(flatten)]
+    pub meta: SongMetadata,
+}
+
+/// Final metadata for every track of an archive at once
+#[derive(Debug, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/FinalAlbumMetadata.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct FinalAlbumMetadata {
+    pub album: Option<Arc<str>>,
+    /// For tracks that don't have their own
+    #[serde(default)]
+    #[ts(as = "Option<Vec<String>>", optional)]
+    pub artists: Vec<Arc<str>>,
+    #[serde(default = "super::add_song::default_storage_backend_name")]
+    #[ts(as = "Option<String>", optional)]
+    pub storage_backend: Arc<str>,
+    /// In the same order as [`ArchiveInfo::tracks`], anything left out is taken from the tags
+    #[serde(default)]
+    #[ts(as = "Option<Vec<FinalTrackMetadata>>", optional)]
+    pub tracks: Vec<FinalTrackMetadata>,
+}
+
+#[derive(Debug, Default, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/FinalTrackMetadata.ts")]
+#[serde(default, rename_all = "camelCase")]
+pub struct FinalTrackMetadata {
+    #[ts(as = "Option<String>", optional)]
+    pub title: Option<Arc<str>>,
+    #[ts(as = "Option<Vec<String>>", optional)]
+    pub artists: Vec<Arc<str>>,
+}
+
+/// Sent when an item of the batch couldn't be imported, the client answers with a
+/// [`ClientMessage::Decision`] and the session goes on with the next item unless it retries
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ItemFailure.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct ItemFailure {
+    /// Position of the item in the batch
+    pub index: usize,
+    pub name: Arc<str>,
+    pub stage: ItemStage,
+    pub error: String,
+    /// Whether it's worth trying again, like after a failed download. Corrupt files aren't.
+    pub retryable: bool,
+}
+
+/// Where importing an item failed
+#[derive(Debug, Clone, Copy, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ItemStage.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum ItemStage {
+    /// Uploading or downloading the file
+    Import,
+    /// Reading its metadata
+    Probe,
+    Corrupt,
+    /// Extracting an album archive
+    Album,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/Decision.ts")]
+#[serde(rename_all = "camelCase")]
+pub enum Decision {
+    Skip,
+    Retry,
+    /// Only for duplicates, adds the song with the file we already have instead of storing it again
+    Link,
+}
+
+#[derive(Debug, Default, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/SummaryInfo.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct SummaryInfo {
+    pub succeeded: Vec<SucceededItem>,
+    /// Items the client skipped, and videos that were already imported without an index
+    pub skipped: Vec<SkippedItem>,
+    pub failed: Vec<FailedItem>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/SucceededItem.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct SucceededItem {
+    pub index: usize,
+    pub name: Arc<str>,
+    /// One per track for album archives
+    #[ts(type = "number[]")]
+    pub song_ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/SkippedItem.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedItem {
+    pub index: Option<usize>,
+    pub name: Arc<str>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/FailedItem.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct FailedItem {
+    /// Null for playlists and channels that couldn't be expanded
+    pub index: Option<usize>,
+    pub name: Arc<str>,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ErrorInfo.ts")]
+pub struct ErrorInfo {
+    pub message: String,
+    /// What went wrong when a song couldn't be added
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub failure: Option<Box<AddSongFailure>>,
+}
+
+impl From<ApiError> for ErrorInfo {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::AddSong(failure) => Self {
+                message: format!("{failure}"),
+                failure: Some(failure),
+            },
+            err => Self {
+                message: format!("{err:?}"),
+                failure: None,
+            },
+        }
+    }
+}
+
+impl ServerMessage {
+    pub async fn send(&self, ws: &mut WebSocket) -> Result<(), ApiError> {
+        ws.send(Message::Text(serde_json::to_string(self)?.into()))
+            .await
+            .map_err(Into::into)
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error(ErrorInfo {
+            message: message.into(),
+            failure: None,
+        })
+    }
+}
+
+impl ClientMessage {
+    /// Waits for the client's next text message
+    pub async fn recv(ws: &mut WebSocket) -> Result<Self, ApiError> {
+        let text = ws
+            .recv()
+            .await
+            .ok_or(ApiError::InvalidWSMessage)??
+            .into_text()?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// Waits for the client's hello and answers it. Clients that don't start with one or speak
+/// another version get an error and the WS is closed, then this returns false.
+pub async fn handshake(ws: &mut WebSocket) -> Result<bool, ApiError> {
+    let error = match ClientMessage::recv(ws).await {
+        Ok(ClientMessage::Hello(Hello { version })) if version == PROTOCOL_VERSION => {
+            ServerMessage::Hello(Hello {
+                version: PROTOCOL_VERSION,
+            })
+            .send(ws)
+            .await?;
+            return Ok(true);
+        }
+        Ok(ClientMessage::Hello(Hello { version })) => format!(
+            "Unsupported protocol version {version}, this server speaks version {PROTOCOL_VERSION}"
+        ),
+        Ok(_) | Err(ApiError::SerdeJson(_)) => format!(
+            "Expected a hello with the protocol version first, this server speaks version {PROTOCOL_VERSION}"
+        ),
+        Err(err) => return Err(err),
+    };
+
+    ServerMessage::error(error.clone()).send(ws).await?;
+    ws.send(Message::Close(Some(CloseFrame {
+        code: close_code::PROTOCOL,
+        reason: error.into(),
+    })))
+    .await?;
+    Ok(false)
+}
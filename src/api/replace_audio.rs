@@ -0,0 +1,230 @@
+use std::sync::Arc;
+
+use axum::{Json, extract};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    ApiError,
+    api::audio::{InitSongInfo, ParsedMetadata, UploadedInitSongInfo},
+    db::{Song, Source, StorageBackend, Tag},
+    storage_path::{PathFields, unused_path},
+};
+
+use super::{
+    State,
+    add_song::{default_storage_backend_name, non_empty, probe_staged, stage_file_field},
+    auth::{AUTH_COOKIE, authenticate},
+    upload::StagedFile,
+};
+
+/// Which song gets a new file, sent over the add songs WS instead of final metadata
+#[derive(Debug, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ReplaceAudio.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceAudio {
+    #[ts(type = "number")]
+    pub song_id: i64,
+    /// Removes the song's other sources, and their objects if no other song uses them
+    #[serde(default)]
+    #[ts(as = "Option<bool>", optional)]
+    pub retire_old: bool,
+    #[serde(default = "default_storage_backend_name")]
+    #[ts(as = "Option<String>", optional)]
+    pub storage_backend: Arc<str>,
+}
+
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ReplaceAudioResult.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceAudioResult {
+    #[ts(type = "number")]
+    pub song_id: i64,
+    #[ts(type = "number")]
+    pub source_id: i64,
+    /// Sources the song doesn't use anymore
+    #[ts(type = "number[]")]
+    pub retired: Vec<i64>,
+    /// Objects of retired sources that couldn't be removed
+    pub left_behind: Vec<String>,
+}
+
+/// Replaces the audio of a song with the multipart form's `file`, keeping its metadata and
+/// tags. `retireOld` set to true removes the old sources, and `storageBackend` picks where the
+/// new file goes.
+pub async fn replace_song_audio(
+    extract::State(state): extract::State<State>,
+    extract::Path(song_id): extract::Path<i64>,
+    cookies: CookieJar,
+    mut multipart: extract::Multipart,
+) -> Result<Json<ReplaceAudioResult>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let mut file = None;
+    let mut file_name = None;
+    let mut replace = ReplaceAudio {
+        song_id,
+        retire_old: false,
+        storage_backend: default_storage_backend_name(),
+    };
+    let res = match read_replace_form(
+        &mut multipart,
+        &state,
+        &mut file,
+        &mut file_name,
+        &mut replace,
+    )
+    .await
+    {
+        Ok(()) => match &file {
+            Some(staged) => replace_from_form(&state, staged, file_name, &replace).await,
+            None => Err(ApiError::BadRequest("Missing file field".into())),
+        },
+        Err(err) => Err(err),
+    };
+
+    if let Some(staged) = file {
+        staged.remove().await;
+    }
+
+    res.map(Json)
+}
+
+async fn read_replace_form(
+    multipart: &mut extract::Multipart,
+    state: &State,
+    file: &mut Option<StagedFile>,
+    file_name: &mut Option<Arc<str>>,
+    replace: &mut ReplaceAudio,
+) -> Result<(), ApiError> {
+    while let Some(mut field) = multipart.next_field().await? {
+        match field.name().unwrap_or_default() {
+            "file" => {
+                *file_name = field.file_name().map(Arc::from);
+                stage_file_field(&mut field, state, file).await?;
+            }
+            "retireOld" => replace.retire_old = field.text().await?.trim() == "true",
+            "storageBackend" => {
+                if let Some(storage_backend) = non_empty(field.text().await?) {
+                    replace.storage_backend = storage_backend;
+                }
+            }
+            name => tracing::debug!("Ignoring unknown form field {name:?}"),
+        }
+    }
+
+    Ok(())
+}
+
+async fn replace_from_form(
+    state: &State,
+    staged: &StagedFile,
+    file_name: Option<Arc<str>>,
+    replace: &ReplaceAudio,
+) -> Result<ReplaceAudioResult, ApiError> {
+    let info = Arc::new(InitSongInfo::Uploaded(UploadedInitSongInfo {
+        name: file_name.unwrap_or_else(|| Arc::from("")),
+        size: tokio::fs::metadata(&staged.path).await?.len() as usize,
+        mime_type: staged.mime_type.clone(),
+        upload_id: None,
+        filename_template: None,
+    }));
+    let (parsed_meta, decode_report) =
+        probe_staged(staged, info, state.config.filename_templates.clone()).await?;
+    if let Some(problem) = decode_report.problem() {
+        return Err(ApiError::BadRequest(format!(
+            "Corrupt audio file: {problem}"
+        )));
+    }
+
+    replace_audio(&state.sqlite, staged, &parsed_meta, replace).await
+}
+
+/// Writes the staged file to the storage backend as a new source of the song. The path uses
+/// the song's own title, album and artists, only track numbers come from the new file.
+pub async fn replace_audio(
+    sqlite: &Pool<Sqlite>,
+    staged: &StagedFile,
+    parsed_meta: &ParsedMetadata,
+    replace: &ReplaceAudio,
+) -> Result<ReplaceAudioResult, ApiError> {
+    let song = Song::get_by_id(replace.song_id, sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let tags = Tag::for_song(song.id, sqlite).await?;
+    let (album, artists) = Tag::album_and_artists(&tags);
+    let old_sources = Source::for_song(song.id, sqlite).await?;
+
+    let storage_backend = StorageBackend::get_by_name(&replace.storage_backend, sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let operator = storage_backend.operator().await?;
+    let content_hash = staged.content_hash().await?.to_string();
+    let path = storage_backend.config.path_template().render(&PathFields {
+        title: &song.title,
+        album: album.as_deref(),
+        album_artist: parsed_meta.album_artist.as_deref(),
+        artist: artists.first().map(|artist| &**artist),
+        disc: parsed_meta.disc,
+        track: parsed_meta.track,
+        mime_type: &staged.mime_type,
+        timestamp: chrono::Utc::now().timestamp(),
+    });
+    let path = unused_path(&operator, path, None).await?;
+
+    let written = staged.write_to(&operator, &path).await;
+    let replaced = match written {
+        Ok(()) => Song::replace_source(
+            song.id,
+            &path,
+            &staged.mime_type,
+            &replace.storage_backend,
+            Some(&content_hash),
+            replace.retire_old,
+            sqlite,
+        )
+        .await
+        .map_err(Into::into),
+        Err(err) => Err(err),
+    };
+    let (source_id, deleted) = match replaced {
+        Ok(replaced) => replaced,
+        Err(err) => {
+            // Nothing points at the new object
+            if let Err(err) = operator.delete(&path).await {
+                tracing::error!("Couldn't roll back {path}: {err:?}");
+            }
+            return Err(err);
+        }
+    };
+
+    let mut left_behind = Vec::new();
+    // Files a library scan added where they were belong to the user's library, not us
+    for source in deleted.into_iter().filter(|source| !source.in_place) {
+        let res = match StorageBackend::operator_by_name(&source.storage_backend_name, sqlite).await
+        {
+            Ok(Some(operator)) => operator.delete(&source.path).await.map_err(Into::into),
+            Ok(None) => Err(ApiError::NotFound),
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = res {
+            tracing::error!("Couldn't remove retired source {}: {err:?}", source.path);
+            left_behind.push(source.path);
+        }
+    }
+
+    Ok(ReplaceAudioResult {
+        song_id: song.id,
+        source_id,
+        retired: if replace.retire_old {
+            old_sources.iter().map(|source| source.id).collect()
+        } else {
+            Vec::new()
+        },
+        left_behind,
+    })
+}
@@ -0,0 +1,103 @@
+use axum::{Json, extract};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ApiError,
+    db::{Song, metadata_diff::MetadataDiff},
+};
+
+use super::{
+    State,
+    auth::{AUTH_COOKIE, authenticate},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyDiffs {
+    /// Apply every pending diff when not given
+    #[serde(default)]
+    song_ids: Option<Vec<i64>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApplyDiffsResult {
+    applied: Vec<i64>,
+    /// Songs that were edited by hand
+    protected: Vec<i64>,
+    not_found: Vec<i64>,
+}
+
+pub async fn get_metadata_diffs(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+) -> Result<Json<Vec<MetadataDiff>>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    Ok(Json(MetadataDiff::get_all(&state.sqlite).await?))
+}
+
+pub async fn apply_metadata_diffs(
+    extract::State(state): extract::State<State>,
+    cookies: CookieJar,
+    Json(apply): Json<ApplyDiffs>,
+) -> Result<Json<ApplyDiffsResult>, ApiError> {
+    let user = authenticate(&state, cookies.get(AUTH_COOKIE)).await?;
+    if !user.admin {
+        return Err(ApiError::Unauthorized);
+    }
+
+    let diffs = match apply.song_ids {
+        Some(song_ids) => {
+            let mut diffs = Vec::with_capacity(song_ids.len());
+            for song_id in song_ids {
+                diffs.push(
+                    MetadataDiff::get_by_song_id(song_id, &state.sqlite)
+                        .await?
+                        .ok_or(song_id),
+                );
+            }
+            diffs
+        }
+        None => MetadataDiff::get_all(&state.sqlite)
+            .await?
+            .into_iter()
+            .map(Ok)
+            .collect(),
+    };
+
+    let mut res = ApplyDiffsResult::default();
+    for diff in diffs {
+        let diff = match diff {
+            Ok(diff) => diff,
+            Err(song_id) => {
+                res.not_found.push(song_id);
+                continue;
+            }
+        };
+
+        if diff.protected {
+            res.protected.push(diff.song_id);
+            continue;
+        }
+
+        let artists = diff.artists.iter().map(String::as_str).collect::<Vec<_>>();
+        Song::set_metadata(
+            diff.song_id,
+            &diff.title,
+            diff.album.as_deref(),
+            &artists,
+            false,
+            &state.sqlite,
+        )
+        .await?;
+        MetadataDiff::delete(diff.song_id, &state.sqlite).await?;
+        res.applied.push(diff.song_id);
+    }
+
+    Ok(Json(res))
+}
@@ -0,0 +1,379 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::{OnceCell, OwnedMutexGuard},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{ApiError, content_hash};
+
+/// Biggest chunk a client may send in one WS message
+pub const MAX_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+/// Biggest request body we take for single request uploads
+pub const MAX_UPLOAD_SIZE: usize = 1024 * 1024 * 1024;
+/// How much of a staged file we read at a time when streaming it into a backend
+const COPY_BUF_SIZE: usize = 1024 * 1024;
+const DATA_FILE: &str = "data";
+const META_FILE: &str = "meta.json";
+const PRESIGNED_FILE: &str = "presigned.json";
+
+/// An audio file on disk waiting to be imported
+#[derive(Debug)]
+pub struct StagedFile {
+    pub path: PathBuf,
+    pub mime_type: Arc<str>,
+    /// Staging dir we own and should remove once the file is imported
+    dir: Option<PathBuf>,
+    /// Hashed the first time it's needed
+    content_hash: OnceCell<String>,
+}
+
+/// Uploads that a WS session is writing to. A client that reconnects while its old session is
+/// still open takes the upload over, so there's never more than one writer appending to it.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveUploads {
+    writers: Arc<Mutex<FxHashMap<Arc<str>, Writer>>>,
+}
+
+#[derive(Debug)]
+struct Writer {
+    lock: Arc<tokio::sync::Mutex<()>>,
+    /// Cancelled when a newer session wants the upload
+    evict: CancellationToken,
+    generation: u64,
+}
+
+/// Held by the session writing to an upload
+#[derive(Debug)]
+pub struct UploadLease {
+    id: Arc<str>,
+    uploads: ActiveUploads,
+    evict: CancellationToken,
+    generation: u64,
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// A partially uploaded file staged under `{data_dir}/uploads/{id}`
+#[derive(Debug)]
+pub struct Upload {
+    pub id: Arc<str>,
+    pub size: u64,
+    pub offset: u64,
+    mime_type: Arc<str>,
+    dir: PathBuf,
+    file: tokio::fs::File,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadMeta {
+    size: u64,
+    mime_type: Arc<str>,
+}
+
+/// An upload the client sends straight to a storage backend with a presigned request,
+/// tracked under `{data_dir}/uploads/{id}` until it's completed
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresignedUpload {
+    pub id: Arc<str>,
+    pub storage_backend: Arc<str>,
+    /// Staging key in the storage backend the client uploads to
+    pub key: String,
+    pub name: Arc<str>,
+    pub mime_type: Arc<str>,
+}
+
+pub fn uploads_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join("uploads")
+}
+
+/// Random hex id that is safe to use as a file name
+pub fn new_id() -> Arc<str> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("OS random number generator failed");
+    Arc::from(bytes.iter().map(|b| format!("{b:02x}")).collect::<String>())
+}
+
+impl ActiveUploads {
+    /// Tells whoever is writing to the upload to stop and waits until they have
+    pub async fn lease(&self, id: &str) -> UploadLease {
+        let id = Arc::<str>::from(id);
+        let (lock, evict, generation) = {
+            let mut writers = self.writers.lock().unwrap();
+            let writer = writers.entry(id.clone()).or_insert_with(|| Writer {
+                lock: Default::default(),
+                evict: CancellationToken::new(),
+                generation: 0,
+            });
+            writer.evict.cancel();
+            writer.evict = CancellationToken::new();
+            writer.generation += 1;
+            (writer.lock.clone(), writer.evict.clone(), writer.generation)
+        };
+
+        UploadLease {
+            _guard: lock.lock_owned().await,
+            id,
+            uploads: self.clone(),
+            evict,
+            generation,
+        }
+    }
+}
+
+impl UploadLease {
+    /// Resolves once a newer session took the upload over
+    pub async fn evicted(&self) {
+        self.evict.cancelled().await
+    }
+}
+
+impl Drop for UploadLease {
+    fn drop(&mut self) {
+        let mut writers = self.uploads.writers.lock().unwrap();
+        // Nobody newer is waiting for it
+        if writers
+            .get(&self.id)
+            .is_some_and(|writer| writer.generation == self.generation)
+        {
+            writers.remove(&self.id);
+        }
+    }
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl StagedFile {
+    /// A file in a staging dir we own, which is removed after importing
+    pub fn owned(path: PathBuf, dir: PathBuf, mime_type: Arc<str>) -> Self {
+        Self {
+            path,
+            mime_type,
+            dir: Some(dir),
+            content_hash: OnceCell::new(),
+        }
+    }
+
+    /// A file we don't own, which is left where it is after importing
+    pub fn external(path: PathBuf, mime_type: Arc<str>) -> Self {
+        Self {
+            path,
+            mime_type,
+            dir: None,
+            content_hash: OnceCell::new(),
+        }
+    }
+
+    /// Creates an empty file in its own staging dir for the caller to write into
+    pub async fn create(
+        data_dir: &Path,
+        mime_type: Arc<str>,
+    ) -> io::Result<(Self, tokio::fs::File)> {
+        let dir = uploads_dir(data_dir).join(&*new_id());
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(DATA_FILE);
+        let file = tokio::fs::File::create(&path).await?;
+
+        Ok((
+            Self {
+                path,
+                mime_type,
+                dir: Some(dir),
+                content_hash: OnceCell::new(),
+            },
+            file,
+        ))
+    }
+
+    /// Streams the file into the backend without loading it all into memory
+    pub async fn write_to(&self, operator: &opendal::Operator, path: &str) -> Result<(), ApiError> {
+        let mut file = tokio::fs::File::open(&self.path).await?;
+        let mut writer = operator.writer(path).await?;
+        let mut buf = vec![0u8; COPY_BUF_SIZE];
+
+        loop {
+            let read = file.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            writer.write(buf[..read].to_vec()).await?;
+        }
+
+        writer.close().await?;
+        Ok(())
+    }
+
+    /// SHA-256 of the file, see [`content_hash`](crate::content_hash)
+    pub async fn content_hash(&self) -> io::Result<&str> {
+        self.content_hash
+            .get_or_try_init(|| content_hash::hash_file(&self.path))
+            .await
+            .map(|hash| &**hash)
+    }
+
+    /// Removes the staging dir, if we own one
+    pub async fn remove(self) {
+        if let Some(dir) = self.dir
+            && let Err(err) = tokio::fs::remove_dir_all(&dir).await
+        {
+            tracing::error!("Couldn't remove staging dir {}: {err:?}", dir.display());
+        }
+    }
+}
+
+impl PresignedUpload {
+    pub fn new(storage_backend: Arc<str>, name: Arc<str>, mime_type: Arc<str>) -> Self {
+        let id = new_id();
+        Self {
+            key: format!("staging/{id}"),
+            id,
+            storage_backend,
+            name,
+            mime_type,
+        }
+    }
+
+    pub async fn save(&self, data_dir: &Path) -> io::Result<()> {
+        let dir = uploads_dir(data_dir).join(&*self.id);
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(dir.join(PRESIGNED_FILE), serde_json::to_vec(self)?).await
+    }
+
+    /// Returns None if there's no presigned upload with this id
+    pub async fn load(data_dir: &Path, id: &str) -> io::Result<Option<Self>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+
+        match tokio::fs::read(uploads_dir(data_dir).join(id).join(PRESIGNED_FILE)).await {
+            Ok(upload) => Ok(Some(serde_json::from_slice(&upload)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Forgets about the upload, the staged object has to be deleted separately
+    pub async fn remove(self, data_dir: &Path) {
+        let dir = uploads_dir(data_dir).join(&*self.id);
+        if let Err(err) = tokio::fs::remove_dir_all(&dir).await {
+            tracing::error!("Couldn't remove staging dir {}: {err:?}", dir.display());
+        }
+    }
+}
+
+/// Moves an object within a backend, for services that can't rename (like S3) it's copied instead
+pub async fn move_object(
+    operator: &opendal::Operator,
+    from: &str,
+    to: &str,
+) -> Result<(), opendal::Error> {
+    if operator.info().full_capability().rename {
+        return operator.rename(from, to).await;
+    }
+
+    operator.copy(from, to).await?;
+    operator.delete(from).await
+}
+
+impl Upload {
+    pub async fn create(data_dir: &Path, size: u64, mime_type: Arc<str>) -> io::Result<Self> {
+        let id = new_id();
+        let dir = uploads_dir(data_dir).join(&*id);
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(
+            dir.join(META_FILE),
+            serde_json::to_vec(&UploadMeta {
+                size,
+                mime_type: mime_type.clone(),
+            })?,
+        )
+        .await?;
+        let file = tokio::fs::File::create(dir.join(DATA_FILE)).await?;
+
+        Ok(Self {
+            id,
+            size,
+            offset: 0,
+            mime_type,
+            dir,
+            file,
+        })
+    }
+
+    /// Picks an upload back up after a client reconnects, returns None if it doesn't exist
+    pub async fn resume(data_dir: &Path, id: &str) -> io::Result<Option<Self>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+
+        let dir = uploads_dir(data_dir).join(id);
+        let meta = match tokio::fs::read(dir.join(META_FILE)).await {
+            Ok(meta) => serde_json::from_slice::<UploadMeta>(&meta)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(DATA_FILE))
+            .await?;
+        let offset = file.metadata().await?.len();
+
+        Ok(Some(Self {
+            id: Arc::from(id),
+            size: meta.size,
+            offset,
+            mime_type: meta.mime_type,
+            dir,
+            file,
+        }))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.offset >= self.size
+    }
+
+    /// Appends a chunk if it starts where the staged data ends, otherwise ignores it
+    /// so the client can re-sync from the offset we report back.
+    pub async fn write_chunk(&mut self, offset: u64, chunk: &[u8]) -> io::Result<()> {
+        if offset != self.offset {
+            tracing::debug!(
+                "Ignoring chunk for {} at {offset}, expected {}",
+                self.id,
+                self.offset
+            );
+            return Ok(());
+        }
+
+        if self.offset + chunk.len() as u64 > self.size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "chunk goes past the declared upload size",
+            ));
+        }
+
+        self.file.write_all(chunk).await?;
+        self.file.flush().await?;
+        self.offset += chunk.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn into_staged(self) -> StagedFile {
+        StagedFile {
+            path: self.dir.join(DATA_FILE),
+            mime_type: self.mime_type,
+            dir: Some(self.dir),
+            content_hash: OnceCell::new(),
+        }
+    }
+}
@@ -3,12 +3,19 @@ use std::{
     net::SocketAddr,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use http::Uri;
 use serde::Deserialize;
 
-use crate::db::{FsConfig, StorageBackendConfig};
+use crate::{
+    api::filename_template::{FilenameTemplate, default_filename_templates},
+    db::{FsConfig, StorageBackendConfig},
+    scheduler::Schedule,
+    watcher::WatchFolder,
+    yt_dlp::YtDlpOptions,
+};
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
@@ -48,11 +55,46 @@ pub struct Config {
 
     #[serde(default = "default_yt_dlp_cookies_path")]
     pub yt_dlp_cookies_path: Arc<str>,
+
+    /// Tried in order to fill in missing tags from the names of uploaded files,
+    /// like `{track} - {artist} - {title}`
+    #[serde(default = "default_filename_templates")]
+    pub filename_templates: Arc<[FilenameTemplate]>,
+
+    /// Default options for yt-dlp downloads
+    #[serde(default)]
+    pub yt_dlp: YtDlpOptions,
+
+    /// How many yt-dlp downloads can run at the same time
+    #[serde(default = "default_yt_dlp_concurrency")]
+    pub yt_dlp_concurrency: usize,
+
+    /// How many background jobs can run at the same time
+    #[serde(default = "default_job_workers")]
+    pub job_workers: usize,
+
+    /// Maintenance tasks to run periodically
+    #[serde(default)]
+    pub schedules: Vec<Schedule>,
+
+    /// How many database backups to keep around in `{data_dir}/backups`
+    #[serde(default = "default_backups_to_keep")]
+    pub backups_to_keep: usize,
+
+    /// Folder songs are imported from automatically
+    #[serde(default)]
+    pub watch_folder: Option<WatchFolder>,
+
+    /// Unfinished uploads older than this are removed by the orphan cleanup task
+    #[serde(default = "default_upload_expiry", with = "humantime_serde")]
+    pub upload_expiry: Duration,
 }
 
 impl Config {
     pub fn from_json(json: &[u8]) -> color_eyre::Result<Self> {
-        Ok(serde_json::from_slice(json)?)
+        let config: Self = serde_json::from_slice(json)?;
+        Schedule::check_all(&config.schedules)?;
+        Ok(config)
     }
 }
 
@@ -129,6 +171,7 @@ fn default_storage_backend() -> Arc<StorageBackendConfig> {
                     .to_str()
                     .unwrap(),
             ),
+            path_template: None,
         }))
     } else {
         panic!("Must set INIT_PASSWORD env variable for production.")
@@ -138,3 +181,19 @@ fn default_storage_backend() -> Arc<StorageBackendConfig> {
 fn default_yt_dlp_cookies_path() -> Arc<str> {
     Arc::from("cookies.txt")
 }
+
+fn default_yt_dlp_concurrency() -> usize {
+    2
+}
+
+fn default_job_workers() -> usize {
+    2
+}
+
+fn default_backups_to_keep() -> usize {
+    7
+}
+
+fn default_upload_expiry() -> Duration {
+    Duration::from_secs(24 * 60 * 60)
+}
@@ -0,0 +1,44 @@
+use std::{io, path::Path};
+
+use futures::TryStreamExt;
+use opendal::Operator;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+
+/// How much of a file we read at a time while hashing it
+const HASH_BUF_SIZE: usize = 1024 * 1024;
+
+/// SHA-256 of a file on our disk as lowercase hex, read a chunk at a time
+pub async fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_BUF_SIZE];
+
+    loop {
+        let read = file.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// SHA-256 of an object in a storage backend as lowercase hex, streamed so it's never all in memory
+pub async fn hash_object(operator: &Operator, path: &str) -> Result<String, opendal::Error> {
+    let mut stream = operator.reader(path).await?.into_bytes_stream(..).await?;
+    let mut hasher = Sha256::new();
+
+    while let Some(chunk) = stream.try_next().await.map_err(|err| {
+        opendal::Error::new(opendal::ErrorKind::Unexpected, "couldn't read object").set_source(err)
+    })? {
+        hasher.update(&chunk);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
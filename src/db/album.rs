@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{prelude::*, Pool};
+use sqlx::prelude::*;
 
 use super::Error;
 
@@ -34,9 +34,41 @@ impl Album {
         .map_err(|e| Error::Select("albums", e))
     }
 
+    /// Sets the album's link, unless it already has one
+    pub async fn fill_link(
+        title: &str,
+        link: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE albums SET link = $1 WHERE title = $2 AND link IS NULL",
+            link,
+            title
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("albums", e))
+        .map(|_| ())
+    }
+
+    /// Whether the album exists and already has a cover image
+    pub async fn has_cover(
+        title: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<bool, Error> {
+        sqlx::query_scalar!(
+            "SELECT cover_image_source_id FROM albums WHERE title = $1",
+            title
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| Error::Select("albums", e))
+        .map(|cover| cover.flatten().is_some())
+    }
+
     pub async fn insert_w_tag<'a>(
         title: &'a str,
-        executor: &Pool<super::DB>,
+        executor: impl Acquire<'_, Database = super::DB>,
     ) -> Result<&'a str, Error> {
         let mut transaction = executor
             .begin()
@@ -70,7 +102,7 @@ impl Album {
         path: &str,
         mime_type: &str,
         backend: &str,
-        executor: &Pool<super::DB>,
+        executor: impl Acquire<'_, Database = super::DB>,
     ) -> Result<&'a str, Error> {
         let mut transaction = executor
             .begin()
@@ -96,7 +128,7 @@ impl Album {
                         )
                             .execute(&mut *transaction)
                             .await
-                            .unwrap()
+                            .map_err(|e| Error::Insert("sources", e))?
                             .last_insert_rowid(),
                         );
         }
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use sqlx::{prelude::*, Pool};
+use sqlx::prelude::*;
 
 use super::Error;
 
@@ -22,9 +22,27 @@ pub struct Artist {
 }
 
 impl Artist {
+    /// Sets the artists' link, for those that don't have one yet
+    pub async fn fill_links(
+        names: &[&str],
+        link: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        let names_json = serde_json::to_string(names).unwrap();
+        sqlx::query!(
+            "UPDATE artists SET link = $1 WHERE name IN (SELECT value FROM json_each($2)) AND link IS NULL",
+            link,
+            names_json
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("artists", e))
+        .map(|_| ())
+    }
+
     pub async fn insert_w_tags<'a, 'b>(
         artists: &'a [&'b str],
-        executor: &Pool<super::DB>,
+        executor: impl Acquire<'_, Database = super::DB>,
     ) -> Result<&'a [&'b str], Error> {
         let mut transaction = executor
             .begin()
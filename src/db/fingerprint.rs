@@ -0,0 +1,109 @@
+use sqlx::prelude::*;
+
+use super::{Error, Source};
+
+/// Acoustic fingerprint of a song's audio, see [`crate::fingerprint`]
+#[derive(Debug, FromRow)]
+pub struct SongFingerprint {
+    pub song_id: i64,
+
+    /// Sub-fingerprints as little endian u32s
+    pub fingerprint: Vec<u8>,
+
+    /// Seconds of audio in the whole song
+    pub duration: f64,
+}
+
+impl SongFingerprint {
+    pub async fn get_all(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            SongFingerprint,
+            "SELECT song_id, fingerprint, duration FROM song_fingerprints"
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("song_fingerprints", e))
+    }
+
+    /// Insert or replace the fingerprint of this song
+    pub async fn upsert(
+        song_id: i64,
+        fingerprint: &[u32],
+        duration: f64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        let fingerprint = fingerprint
+            .iter()
+            .flat_map(|hash| hash.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO song_fingerprints (song_id, fingerprint, duration) VALUES ($1, $2, $3)
+            ON CONFLICT (song_id) DO UPDATE SET
+                fingerprint = excluded.fingerprint,
+                duration = excluded.duration,
+                created_at = CURRENT_TIMESTAMP
+            "#,
+            song_id,
+            fingerprint,
+            duration
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("song_fingerprints", e))
+        .map(|_| ())
+    }
+
+    /// Songs without a fingerprint, each with its first audio source
+    pub async fn get_missing(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<(i64, Source)>, Error> {
+        sqlx::query!(
+            r#"
+            SELECT sts.song_id AS "song_id!", s.* FROM songs_to_sources sts
+            JOIN songs ON songs.id = sts.song_id
+            JOIN sources s ON s.id = sts.source_id
+            WHERE s.mime_type LIKE 'audio/%'
+                AND NOT EXISTS (SELECT 1 FROM song_fingerprints f WHERE f.song_id = sts.song_id)
+                AND s.id = (
+                    SELECT MIN(o.id) FROM songs_to_sources osts
+                    JOIN sources o ON o.id = osts.source_id
+                    WHERE osts.song_id = sts.song_id AND o.mime_type LIKE 'audio/%'
+                )
+            ORDER BY sts.song_id
+            "#
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("song_fingerprints", e))
+        .map(|rows| {
+            rows.into_iter()
+                .map(|row| {
+                    (
+                        row.song_id,
+                        Source {
+                            id: row.id,
+                            path: row.path,
+                            mime_type: row.mime_type,
+                            storage_backend_name: row.storage_backend_name,
+                            content_hash: row.content_hash,
+                            in_place: row.in_place,
+                            created_at: row.created_at,
+                            updated_at: row.updated_at,
+                        },
+                    )
+                })
+                .collect()
+        })
+    }
+
+    pub fn hashes(&self) -> Vec<u32> {
+        self.fingerprint
+            .chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+}
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::*;
+
+use super::Error;
+
+/// A background task and its progress
+#[derive(Debug, FromRow, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/Job.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    #[ts(type = "number")]
+    pub id: i64,
+
+    pub kind: String,
+
+    /// JSON of the parameters the job was started with
+    pub params: String,
+
+    pub status: JobStatus,
+
+    #[ts(type = "number")]
+    pub progress: i64,
+
+    #[ts(type = "number | null")]
+    pub total: Option<i64>,
+
+    pub error: Option<String>,
+
+    pub created_by: Option<String>,
+
+    pub started_at: Option<chrono::NaiveDateTime>,
+
+    pub finished_at: Option<chrono::NaiveDateTime>,
+
+    #[serde(skip_deserializing)]
+    pub created_at: chrono::NaiveDateTime,
+
+    #[serde(skip_deserializing)]
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/JobStatus.ts")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/JobLog.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct JobLog {
+    #[ts(type = "number")]
+    pub id: i64,
+
+    #[ts(type = "number")]
+    pub job_id: i64,
+
+    pub level: String,
+
+    pub message: String,
+
+    #[serde(skip_deserializing)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+/// Outcome for a single item a job worked on
+#[derive(Debug, FromRow, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/JobResult.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct JobResult {
+    #[ts(type = "number")]
+    pub id: i64,
+
+    #[ts(type = "number")]
+    pub job_id: i64,
+
+    pub item: String,
+
+    pub ok: bool,
+
+    pub detail: Option<String>,
+
+    #[serde(skip_deserializing)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl Job {
+    pub async fn get_all(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            Job,
+            r#"SELECT id, kind, params, status as "status: JobStatus", progress, total, error, created_by, started_at, finished_at, created_at, updated_at
+            FROM jobs ORDER BY id DESC"#
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("jobs", e))
+    }
+
+    pub async fn get_by_id(
+        id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Option<Self>, Error> {
+        sqlx::query_as!(
+            Job,
+            r#"SELECT id, kind, params, status as "status: JobStatus", progress, total, error, created_by, started_at, finished_at, created_at, updated_at
+            FROM jobs WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| Error::Select("jobs", e))
+    }
+
+    pub async fn insert(
+        kind: &str,
+        params: &str,
+        created_by: Option<&str>,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<i64, Error> {
+        sqlx::query!(
+            "INSERT INTO jobs (kind, params, created_by) VALUES ($1, $2, $3)",
+            kind,
+            params,
+            created_by
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("jobs", e))
+        .map(|res| res.last_insert_rowid())
+    }
+
+    pub async fn set_running(
+        id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE jobs SET status = 'running', started_at = CURRENT_TIMESTAMP WHERE id = $1",
+            id
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("jobs", e))
+        .map(|_| ())
+    }
+
+    pub async fn set_finished(
+        id: i64,
+        status: JobStatus,
+        error: Option<&str>,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE jobs SET status = $1, error = $2, finished_at = CURRENT_TIMESTAMP WHERE id = $3",
+            status,
+            error,
+            id
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("jobs", e))
+        .map(|_| ())
+    }
+
+    pub async fn set_progress(
+        id: i64,
+        progress: i64,
+        total: Option<i64>,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE jobs SET progress = $1, total = COALESCE($2, total) WHERE id = $3",
+            progress,
+            total,
+            id
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("jobs", e))
+        .map(|_| ())
+    }
+
+    /// Jobs that were queued or running when the server stopped can't be continued, so fail them
+    pub async fn fail_unfinished(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<u64, Error> {
+        sqlx::query!(
+            r#"
+            UPDATE jobs SET status = 'failed', error = 'interrupted by server restart', finished_at = CURRENT_TIMESTAMP
+            WHERE status IN ('queued', 'running')
+            "#
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("jobs", e))
+        .map(|res| res.rows_affected())
+    }
+}
+
+impl JobLog {
+    pub async fn for_job(
+        job_id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            JobLog,
+            "SELECT * FROM job_logs WHERE job_id = $1 ORDER BY id",
+            job_id
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("job_logs", e))
+    }
+
+    pub async fn insert(
+        job_id: i64,
+        level: &str,
+        message: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "INSERT INTO job_logs (job_id, level, message) VALUES ($1, $2, $3)",
+            job_id,
+            level,
+            message
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("job_logs", e))
+        .map(|_| ())
+    }
+}
+
+impl JobResult {
+    /// Latest results of every job of a kind, newest first
+    pub async fn latest_for_kind(
+        kind: &str,
+        limit: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            JobResult,
+            "SELECT r.* FROM job_results r JOIN jobs j ON j.id = r.job_id WHERE j.kind = $1 ORDER BY r.id DESC LIMIT $2",
+            kind,
+            limit
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("job_results", e))
+    }
+
+    pub async fn for_job(
+        job_id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            JobResult,
+            "SELECT * FROM job_results WHERE job_id = $1 ORDER BY id",
+            job_id
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("job_results", e))
+    }
+
+    pub async fn insert(
+        job_id: i64,
+        item: &str,
+        ok: bool,
+        detail: Option<&str>,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "INSERT INTO job_results (job_id, item, ok, detail) VALUES ($1, $2, $3, $4)",
+            job_id,
+            item,
+            ok,
+            detail
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("job_results", e))
+        .map(|_| ())
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::*;
+
+use super::Error;
+
+/// Metadata diff in the db, artists are stored as JSON arrays
+#[derive(Debug, FromRow)]
+struct DBMetadataDiff {
+    song_id: i64,
+    job_id: Option<i64>,
+    current_title: String,
+    current_album: Option<String>,
+    current_artists: String,
+    title: String,
+    album: Option<String>,
+    artists: String,
+    metadata_edited: bool,
+    created_at: chrono::NaiveDateTime,
+}
+
+/// Difference between a song's metadata and what a re-scan parsed from its file
+#[derive(Debug, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/MetadataDiff.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataDiff {
+    #[ts(type = "number")]
+    pub song_id: i64,
+
+    #[ts(type = "number | null")]
+    pub job_id: Option<i64>,
+
+    pub current_title: String,
+
+    pub current_album: Option<String>,
+
+    pub current_artists: Vec<String>,
+
+    pub title: String,
+
+    pub album: Option<String>,
+
+    pub artists: Vec<String>,
+
+    /// Song was edited by hand, so this diff won't be applied
+    #[serde(skip_deserializing)]
+    pub protected: bool,
+
+    #[serde(skip_deserializing)]
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl DBMetadataDiff {
+    fn parse(self) -> Result<MetadataDiff, Error> {
+        Ok(MetadataDiff {
+            song_id: self.song_id,
+            job_id: self.job_id,
+            current_title: self.current_title,
+            current_album: self.current_album,
+            current_artists: serde_json::from_str(&self.current_artists)
+                .map_err(|e| Error::Deserialize("metadata_diffs", e))?,
+            title: self.title,
+            album: self.album,
+            artists: serde_json::from_str(&self.artists)
+                .map_err(|e| Error::Deserialize("metadata_diffs", e))?,
+            protected: self.metadata_edited,
+            created_at: self.created_at,
+        })
+    }
+}
+
+impl MetadataDiff {
+    pub async fn get_all(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            DBMetadataDiff,
+            "SELECT d.*, s.metadata_edited FROM metadata_diffs d JOIN songs s ON s.id = d.song_id"
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("metadata_diffs", e))?
+        .into_iter()
+        .map(DBMetadataDiff::parse)
+        .collect()
+    }
+
+    pub async fn get_by_song_id(
+        song_id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Option<Self>, Error> {
+        sqlx::query_as!(
+            DBMetadataDiff,
+            "SELECT d.*, s.metadata_edited FROM metadata_diffs d JOIN songs s ON s.id = d.song_id WHERE d.song_id = $1",
+            song_id
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| Error::Select("metadata_diffs", e))?
+        .map(DBMetadataDiff::parse)
+        .transpose()
+    }
+
+    pub async fn upsert(
+        &self,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        let current_artists = serde_json::to_string(&self.current_artists).unwrap();
+        let artists = serde_json::to_string(&self.artists).unwrap();
+        sqlx::query!(
+            r#"
+            INSERT OR REPLACE INTO metadata_diffs (song_id, job_id, current_title, current_album, current_artists, title, album, artists)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            self.song_id,
+            self.job_id,
+            self.current_title,
+            self.current_album,
+            current_artists,
+            self.title,
+            self.album,
+            artists
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("metadata_diffs", e))
+        .map(|_| ())
+    }
+
+    pub async fn delete(
+        song_id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!("DELETE FROM metadata_diffs WHERE song_id = $1", song_id)
+            .execute(executor)
+            .await
+            .map_err(|e| Error::Delete("metadata_diffs", e))
+            .map(|_| ())
+    }
+}
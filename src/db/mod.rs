@@ -3,11 +3,18 @@ use thiserror::Error;
 
 pub mod album;
 pub mod artist;
+pub mod fingerprint;
+pub mod job;
+pub mod metadata_diff;
+pub mod provenance;
+pub mod review;
+pub mod scheduled_task;
 pub mod song;
 pub mod source;
 pub mod storage_backend;
 pub mod tag;
 pub mod user;
+pub mod verification;
 
 pub use album::Album;
 pub use artist::Artist;
@@ -16,6 +23,7 @@ pub use source::Source;
 pub use storage_backend::{FsConfig, StorageBackend, StorageBackendConfig};
 pub use tag::Tag;
 pub use user::User;
+pub use verification::SourceVerification;
 
 type DB = Sqlite;
 
@@ -37,6 +45,19 @@ pub enum Error {
     Deserialize(&'static str, serde_json::Error),
 }
 
+/// Writes a consistent copy of the whole database to `path`
+pub async fn backup_to(
+    path: &str,
+    executor: impl sqlx::Executor<'_, Database = DB>,
+) -> Result<(), Error> {
+    sqlx::query("VACUUM INTO $1")
+        .bind(path)
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Transaction("backup", e))
+        .map(|_| ())
+}
+
 impl Error {
     /// Panics if this is an invalid row error
     pub fn into_sqlx_error(self) -> sqlx::Error {
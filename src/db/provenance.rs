@@ -0,0 +1,154 @@
+use rustc_hash::FxHashSet;
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::*;
+
+use super::Error;
+
+/// Where a song came from and who added it
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/SongProvenance.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct SongProvenance {
+    #[ts(type = "number")]
+    pub song_id: i64,
+
+    pub uploaded_by: Option<String>,
+
+    pub method: ImportMethod,
+
+    /// Original filename, or the url it was downloaded from
+    pub original_name: Option<String>,
+
+    pub video_id: Option<String>,
+
+    pub channel: Option<String>,
+
+    pub channel_url: Option<String>,
+
+    /// As yt-dlp gives it, i.e. "20240131"
+    pub upload_date: Option<String>,
+
+    pub webpage_url: Option<String>,
+
+    /// Playlist or channel the song was imported from
+    pub playlist_url: Option<String>,
+
+    #[serde(skip_deserializing)]
+    pub imported_at: chrono::NaiveDateTime,
+}
+
+/// How a song was added
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ImportMethod.ts")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+#[serde(rename_all = "camelCase")]
+pub enum ImportMethod {
+    /// Uploaded over the add songs WS
+    Ws,
+    /// Uploaded with the multipart form endpoint
+    Form,
+    /// Uploaded straight to a storage backend
+    Presigned,
+    /// Downloaded with yt-dlp
+    Yt,
+    /// Downloaded from a http(s) url
+    Url,
+    /// Found by a library scan, the file stays where it was
+    Scan,
+    /// Dropped into the watch folder
+    Watch,
+}
+
+impl ImportMethod {
+    /// Nobody confirmed the metadata of songs added this way, so they wait for review
+    pub fn needs_review(self) -> bool {
+        match self {
+            ImportMethod::Form
+            | ImportMethod::Presigned
+            | ImportMethod::Scan
+            | ImportMethod::Watch => true,
+            ImportMethod::Ws | ImportMethod::Yt | ImportMethod::Url => false,
+        }
+    }
+}
+
+impl SongProvenance {
+    /// Provenance for a song that's about to be added, the song id is filled in once it's inserted
+    pub fn new(
+        method: ImportMethod,
+        uploaded_by: Option<&str>,
+        original_name: Option<&str>,
+    ) -> Self {
+        Self {
+            song_id: 0,
+            uploaded_by: uploaded_by.map(str::to_string),
+            method,
+            original_name: original_name.map(str::to_string),
+            video_id: None,
+            channel: None,
+            channel_url: None,
+            upload_date: None,
+            webpage_url: None,
+            playlist_url: None,
+            imported_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub async fn get_by_song_id(
+        song_id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Option<Self>, Error> {
+        sqlx::query_as!(
+            SongProvenance,
+            r#"SELECT song_id, uploaded_by, method as "method: ImportMethod", original_name, video_id, channel, channel_url, upload_date, webpage_url, playlist_url, imported_at
+            FROM song_provenance WHERE song_id = $1"#,
+            song_id
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| Error::Select("song_provenance", e))
+    }
+
+    pub async fn insert(
+        &self,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            INSERT OR REPLACE INTO song_provenance (song_id, uploaded_by, method, original_name, video_id, channel, channel_url, upload_date, webpage_url, playlist_url)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            self.song_id,
+            self.uploaded_by,
+            self.method,
+            self.original_name,
+            self.video_id,
+            self.channel,
+            self.channel_url,
+            self.upload_date,
+            self.webpage_url,
+            self.playlist_url
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("song_provenance", e))
+        .map(|_| ())
+    }
+
+    /// Which of these YouTube video ids were already imported
+    pub async fn imported_video_ids(
+        video_ids: &[&str],
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<FxHashSet<String>, Error> {
+        // Playlists can be huge, so pass the ids as JSON instead of a param each
+        let video_ids_json = serde_json::to_string(video_ids).unwrap();
+        sqlx::query_scalar!(
+            r#"SELECT video_id as "video_id!" FROM song_provenance WHERE video_id IN (SELECT value FROM json_each($1))"#,
+            video_ids_json
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("song_provenance", e))
+        .map(|ids| ids.into_iter().collect())
+    }
+}
@@ -0,0 +1,147 @@
+use serde::Serialize;
+use sqlx::prelude::*;
+
+use super::Error;
+
+/// Pending review in the db, artists are stored as a JSON array
+#[derive(Debug, FromRow)]
+struct DBPendingReview {
+    song_id: i64,
+    title: String,
+    album: Option<String>,
+    artists: String,
+    album_artist: Option<String>,
+    track: Option<i64>,
+    disc: Option<i64>,
+    created_at: chrono::NaiveDateTime,
+}
+
+/// Metadata a song was imported with while it waits for an admin to approve it
+#[derive(Debug, Serialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/PendingReview.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct PendingReview {
+    #[ts(type = "number")]
+    pub song_id: i64,
+
+    pub title: String,
+
+    pub album: Option<String>,
+
+    pub artists: Vec<String>,
+
+    pub album_artist: Option<String>,
+
+    #[ts(type = "number | null")]
+    pub track: Option<i64>,
+
+    #[ts(type = "number | null")]
+    pub disc: Option<i64>,
+
+    pub created_at: chrono::NaiveDateTime,
+}
+
+impl DBPendingReview {
+    fn parse(self) -> Result<PendingReview, Error> {
+        Ok(PendingReview {
+            song_id: self.song_id,
+            title: self.title,
+            album: self.album,
+            artists: serde_json::from_str(&self.artists)
+                .map_err(|e| Error::Deserialize("pending_reviews", e))?,
+            album_artist: self.album_artist,
+            track: self.track,
+            disc: self.disc,
+            created_at: self.created_at,
+        })
+    }
+}
+
+impl PendingReview {
+    pub async fn get_all(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(DBPendingReview, "SELECT * FROM pending_reviews")
+            .fetch_all(executor)
+            .await
+            .map_err(|e| Error::Select("pending_reviews", e))?
+            .into_iter()
+            .map(DBPendingReview::parse)
+            .collect()
+    }
+
+    /// Marks the song as pending review and records what it was imported with
+    pub async fn insert(
+        &self,
+        executor: impl Acquire<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        let mut transaction = executor
+            .begin()
+            .await
+            .map_err(|e| Error::Transaction("pending_reviews", e))?;
+
+        sqlx::query!(
+            "UPDATE songs SET pending_review = TRUE WHERE id = $1",
+            self.song_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Update("songs", e))?;
+
+        let artists = serde_json::to_string(&self.artists).unwrap();
+        sqlx::query!(
+            r#"
+            INSERT OR REPLACE INTO pending_reviews (song_id, title, album, artists, album_artist, track, disc)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            self.song_id,
+            self.title,
+            self.album,
+            artists,
+            self.album_artist,
+            self.track,
+            self.disc
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Insert("pending_reviews", e))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| Error::Transaction("pending_reviews", e))
+    }
+
+    /// Lets the song into the library, returns false if it wasn't pending review
+    pub async fn approve(
+        song_id: i64,
+        executor: impl Acquire<'_, Database = super::DB>,
+    ) -> Result<bool, Error> {
+        let mut transaction = executor
+            .begin()
+            .await
+            .map_err(|e| Error::Transaction("pending_reviews", e))?;
+
+        let approved = sqlx::query!(
+            "UPDATE songs SET pending_review = FALSE WHERE id = $1 AND pending_review",
+            song_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Update("songs", e))?
+        .rows_affected()
+            > 0;
+
+        sqlx::query!("DELETE FROM pending_reviews WHERE song_id = $1", song_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Delete("pending_reviews", e))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| Error::Transaction("pending_reviews", e))?;
+
+        Ok(approved)
+    }
+}
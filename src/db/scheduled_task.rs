@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::*;
+
+use super::{Error, job::JobStatus};
+
+/// A maintenance task that runs on an interval and how its last run went
+#[derive(Debug, FromRow, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/ScheduledTask.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledTask {
+    pub name: String,
+
+    #[ts(type = "number")]
+    pub every_secs: i64,
+
+    #[ts(type = "number | null")]
+    pub last_job_id: Option<i64>,
+
+    pub last_run_at: Option<chrono::NaiveDateTime>,
+
+    pub last_status: Option<JobStatus>,
+
+    pub last_error: Option<String>,
+
+    pub next_run_at: Option<chrono::NaiveDateTime>,
+
+    #[serde(skip_deserializing)]
+    pub created_at: chrono::NaiveDateTime,
+
+    #[serde(skip_deserializing)]
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl ScheduledTask {
+    pub async fn get_all(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            ScheduledTask,
+            r#"SELECT name, every_secs, last_job_id, last_run_at, last_status as "last_status: JobStatus", last_error, next_run_at, created_at, updated_at
+            FROM scheduled_tasks"#
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("scheduled_tasks", e))
+    }
+
+    pub async fn get_by_name(
+        name: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Option<Self>, Error> {
+        sqlx::query_as!(
+            ScheduledTask,
+            r#"SELECT name, every_secs, last_job_id, last_run_at, last_status as "last_status: JobStatus", last_error, next_run_at, created_at, updated_at
+            FROM scheduled_tasks WHERE name = $1"#,
+            name
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| Error::Select("scheduled_tasks", e))
+    }
+
+    pub async fn upsert(
+        name: &str,
+        every_secs: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO scheduled_tasks (name, every_secs) VALUES ($1, $2)
+            ON CONFLICT (name) DO UPDATE SET every_secs = excluded.every_secs
+            "#,
+            name,
+            every_secs
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("scheduled_tasks", e))
+        .map(|_| ())
+    }
+
+    /// Removes every task that isn't in `names`, for when it's removed from the config
+    pub async fn delete_all_except(
+        names: &[&str],
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        // Small enough list to just pass as JSON
+        let names_json = serde_json::to_string(names).unwrap();
+        sqlx::query!(
+            "DELETE FROM scheduled_tasks WHERE name NOT IN (SELECT value FROM json_each($1))",
+            names_json
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Delete("scheduled_tasks", e))
+        .map(|_| ())
+    }
+
+    pub async fn set_next_run(
+        name: &str,
+        next_run_at: chrono::NaiveDateTime,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE scheduled_tasks SET next_run_at = $1 WHERE name = $2",
+            next_run_at,
+            name
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("scheduled_tasks", e))
+        .map(|_| ())
+    }
+
+    pub async fn record_run(
+        name: &str,
+        job_id: i64,
+        ran_at: chrono::NaiveDateTime,
+        status: JobStatus,
+        error: Option<&str>,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            UPDATE scheduled_tasks SET last_job_id = $1, last_run_at = $2, last_status = $3, last_error = $4
+            WHERE name = $5
+            "#,
+            job_id,
+            ran_at,
+            status,
+            error,
+            name
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("scheduled_tasks", e))
+        .map(|_| ())
+    }
+}
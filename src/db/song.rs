@@ -2,7 +2,7 @@ use itertools::intersperse;
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::*, Pool, Sqlite};
 
-use super::Error;
+use super::{Error, Source};
 
 #[derive(Debug, FromRow, Serialize, Deserialize, ts_rs::TS)]
 #[ts(export, export_to = "../web/src/types/Song.ts")]
@@ -13,6 +13,14 @@ pub struct Song {
 
     pub title: String,
 
+    /// Set when an admin edits the metadata by hand, so re-scans won't overwrite it
+    #[serde(default)]
+    pub metadata_edited: bool,
+
+    /// Set when a non-interactive import added it, the song isn't listed until it's approved
+    #[serde(default)]
+    pub pending_review: bool,
+
     #[serde(skip_deserializing)]
     pub created_at: chrono::NaiveDateTime,
 
@@ -38,7 +46,9 @@ impl Song {
             .map_err(|e| Error::Select("songs", e))
     }
 
+    /// Songs pending review are only included with `include_pending`
     pub async fn get_all_with_tags(
+        include_pending: bool,
         executor: impl Executor<'_, Database = super::DB>,
     ) -> Result<Vec<SongWTags>, Error> {
         let records = sqlx::query!(
@@ -47,8 +57,10 @@ impl Song {
             FROM songs s
             LEFT JOIN songs_to_tags stt ON s.id = stt.song_id
             LEFT JOIN tags t ON stt.tag_id = t.name
+            WHERE $1 OR NOT s.pending_review
             GROUP BY s.id
             "#,
+            include_pending
         )
         .fetch_all(executor)
         .await
@@ -60,6 +72,8 @@ impl Song {
                 song: Song {
                     id: r.id,
                     title: r.title,
+                    metadata_edited: r.metadata_edited,
+                    pending_review: r.pending_review,
                     created_at: r.created_at,
                     updated_at: r.updated_at,
                 },
@@ -90,7 +104,9 @@ impl Song {
         path: &str,
         mime_type: &str,
         backend: &str,
-        executor: &Pool<Sqlite>,
+        content_hash: Option<&str>,
+        in_place: bool,
+        executor: impl Acquire<'_, Database = super::DB>,
     ) -> Result<i64, Error> {
         let mut transaction = executor
             .begin()
@@ -100,19 +116,21 @@ impl Song {
         let song_id = sqlx::query!("INSERT INTO songs (title) VALUES ($1)", title)
             .execute(&mut *transaction)
             .await
-            .unwrap()
+            .map_err(|e| Error::Insert("songs", e))?
             .last_insert_rowid();
 
         sqlx::query!(
             r#"
     -- Insert into sources and store the source_id
-    INSERT INTO sources (path, mime_type, storage_backend_name) VALUES ($1, $2, $3);
+    INSERT INTO sources (path, mime_type, storage_backend_name, content_hash, in_place) VALUES ($1, $2, $3, $4, $5);
     -- Insert into songs_to_sources using both IDs
-    INSERT INTO songs_to_sources (song_id, source_id) VALUES ($4, last_insert_rowid());
+    INSERT INTO songs_to_sources (song_id, source_id) VALUES ($6, last_insert_rowid());
         "#,
             path,
             mime_type,
             backend,
+            content_hash,
+            in_place,
             song_id
         )
         .execute(&mut *transaction)
@@ -128,7 +146,119 @@ impl Song {
         Ok(song_id)
     }
 
-    pub async fn delete_w_sources(id: i64, executor: &Pool<Sqlite>) -> Result<(), Error> {
+    /// Adds a song that uses a source another song already has, like a duplicate of it
+    pub async fn insert_w_existing_source(
+        title: &str,
+        source_id: i64,
+        executor: impl Acquire<'_, Database = super::DB>,
+    ) -> Result<i64, Error> {
+        let mut transaction = executor
+            .begin()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        let song_id = sqlx::query!("INSERT INTO songs (title) VALUES ($1)", title)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Insert("songs", e))?
+            .last_insert_rowid();
+
+        sqlx::query!(
+            "INSERT INTO songs_to_sources (song_id, source_id) VALUES ($1, $2)",
+            song_id,
+            source_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Insert("songs_to_sources", e))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        Ok(song_id)
+    }
+
+    /// Replaces the title, album tag and artist tags of a song, creating albums and artists as needed
+    pub async fn set_metadata(
+        id: i64,
+        title: &str,
+        album: Option<&str>,
+        artists: &[&str],
+        edited: bool,
+        executor: &Pool<Sqlite>,
+    ) -> Result<(), Error> {
+        let mut transaction = executor
+            .begin()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        sqlx::query!(
+            "UPDATE songs SET title = $1, metadata_edited = $2 WHERE id = $3",
+            title,
+            edited,
+            id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Update("songs", e))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM songs_to_tags WHERE song_id = $1 AND tag_id IN (
+                SELECT name FROM tags WHERE album_id IS NOT NULL OR artist_id IS NOT NULL
+            )
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Delete("songs_to_tags", e))?;
+
+        if let Some(album) = album {
+            sqlx::query!("INSERT OR IGNORE INTO albums (title) VALUES ($1)", album)
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| Error::Insert("albums", e))?;
+            sqlx::query!(
+                "INSERT OR IGNORE INTO tags(name, album_id) VALUES ($1, $2)",
+                album,
+                album,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Insert("tags", e))?;
+            Self::add_tag(id, album, &mut *transaction).await?;
+        }
+
+        for artist in artists {
+            sqlx::query!("INSERT OR IGNORE INTO artists (name) VALUES ($1)", artist)
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| Error::Insert("artists", e))?;
+            sqlx::query!(
+                "INSERT OR IGNORE INTO tags(name, artist_id) VALUES ($1, $2)",
+                artist,
+                artist
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Insert("tags", e))?;
+            Self::add_tag(id, artist, &mut *transaction).await?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        Ok(())
+    }
+
+    /// Deletes the song. Sources no other song uses are left for the orphan cleanup, which
+    /// removes their objects too, except scanned ones in place that are only rows.
+    pub async fn delete(id: i64, executor: &Pool<Sqlite>) -> Result<(), Error> {
         let mut transaction = executor
             .begin()
             .await
@@ -138,6 +268,9 @@ impl Song {
             r#"
             WITH to_delete AS (
                 SELECT s.id FROM songs_to_sources sts JOIN sources s ON sts.source_id = s.id WHERE sts.song_id = $1
+                    AND s.in_place
+                    -- Sources linked to another song as a duplicate stay for that song
+                    AND NOT EXISTS (SELECT 1 FROM songs_to_sources o WHERE o.source_id = s.id AND o.song_id != $1)
             )
             DELETE FROM sources WHERE id in (SELECT id FROM to_delete);
         "#,
@@ -161,6 +294,146 @@ impl Song {
         Ok(())
     }
 
+    /// Adds a new source to the song, its tags stay as they are. With `retire_old` every other
+    /// source is unlinked from the song and its fingerprint is dropped so it's redone from the
+    /// new audio. Returns the new source's id and the old sources that were deleted since no
+    /// other song uses them, their objects still have to be removed unless they're in place.
+    pub async fn replace_source(
+        id: i64,
+        path: &str,
+        mime_type: &str,
+        backend: &str,
+        content_hash: Option<&str>,
+        retire_old: bool,
+        executor: &Pool<Sqlite>,
+    ) -> Result<(i64, Vec<Source>), Error> {
+        let mut transaction = executor
+            .begin()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        let source_id = sqlx::query!(
+            "INSERT INTO sources (path, mime_type, storage_backend_name, content_hash) VALUES ($1, $2, $3, $4)",
+            path,
+            mime_type,
+            backend,
+            content_hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Insert("sources", e))?
+        .last_insert_rowid();
+
+        sqlx::query!(
+            "INSERT INTO songs_to_sources (song_id, source_id) VALUES ($1, $2)",
+            id,
+            source_id
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| Error::Insert("songs_to_sources", e))?;
+
+        let mut deleted = Vec::new();
+        if retire_old {
+            deleted = sqlx::query_as!(
+                Source,
+                r#"
+                SELECT s.* FROM songs_to_sources sts JOIN sources s ON s.id = sts.source_id
+                WHERE sts.song_id = $1 AND s.id != $2
+                    AND NOT EXISTS (SELECT 1 FROM songs_to_sources o WHERE o.source_id = s.id AND o.song_id != $1)
+                "#,
+                id,
+                source_id
+            )
+            .fetch_all(&mut *transaction)
+            .await
+            .map_err(|e| Error::Select("sources", e))?;
+
+            sqlx::query!(
+                "DELETE FROM songs_to_sources WHERE song_id = $1 AND source_id != $2",
+                id,
+                source_id
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Delete("songs_to_sources", e))?;
+
+            for source in &deleted {
+                sqlx::query!("DELETE FROM sources WHERE id = $1", source.id)
+                    .execute(&mut *transaction)
+                    .await
+                    .map_err(|e| Error::Delete("sources", e))?;
+            }
+
+            sqlx::query!("DELETE FROM song_fingerprints WHERE song_id = $1", id)
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| Error::Delete("song_fingerprints", e))?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        Ok((source_id, deleted))
+    }
+
+    /// Moves the sources of `others` to `id` and deletes them. Tags that aren't an album or
+    /// artist are kept, and the song keeps its own metadata. A song without provenance takes
+    /// the first one it finds, so already imported videos are still skipped.
+    pub async fn merge(id: i64, others: &[i64], executor: &Pool<Sqlite>) -> Result<(), Error> {
+        let mut transaction = executor
+            .begin()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        for other in others {
+            sqlx::query!(
+                "INSERT OR IGNORE INTO songs_to_sources (song_id, source_id) SELECT $1, source_id FROM songs_to_sources WHERE song_id = $2",
+                id,
+                other
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Insert("songs_to_sources", e))?;
+
+            sqlx::query!(
+                r#"
+                INSERT OR IGNORE INTO songs_to_tags (song_id, tag_id)
+                SELECT $1, stt.tag_id FROM songs_to_tags stt JOIN tags t ON t.name = stt.tag_id
+                WHERE stt.song_id = $2 AND t.album_id IS NULL AND t.artist_id IS NULL
+                "#,
+                id,
+                other
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Insert("songs_to_tags", e))?;
+
+            sqlx::query!(
+                "UPDATE OR IGNORE song_provenance SET song_id = $1 WHERE song_id = $2",
+                id,
+                other
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| Error::Update("song_provenance", e))?;
+
+            sqlx::query!("DELETE FROM songs WHERE id = $1", other)
+                .execute(&mut *transaction)
+                .await
+                .map_err(|e| Error::Delete("songs", e))?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| Error::Transaction("songs", e))?;
+
+        Ok(())
+    }
+
     pub async fn add_tag(
         song_id: i64,
         tag: &str,
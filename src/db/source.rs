@@ -3,7 +3,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::*;
 use tokio::sync::RwLock;
@@ -24,6 +24,14 @@ pub struct Source {
 
     pub storage_backend_name: String,
 
+    /// SHA-256 of the data, used to find duplicates. Null until it's hashed.
+    #[serde(skip_deserializing)]
+    pub content_hash: Option<String>,
+
+    /// Added by a library scan where it already was, so we don't move or delete its object
+    #[serde(skip_deserializing)]
+    pub in_place: bool,
+
     #[serde(skip_deserializing)]
     pub created_at: chrono::NaiveDateTime,
 
@@ -91,6 +99,8 @@ impl Source {
                 path: record.path,
                 mime_type: record.mime_type,
                 storage_backend_name: record.storage_backend_name,
+                content_hash: record.content_hash,
+                in_place: record.in_place,
                 created_at: record.created_at,
                 updated_at: record.updated_at,
             };
@@ -118,6 +128,8 @@ impl Source {
                 path: record.path,
                 mime_type: record.mime_type,
                 storage_backend_name: record.storage_backend_name,
+                content_hash: record.content_hash,
+                in_place: record.in_place,
                 created_at: record.created_at,
                 updated_at: record.updated_at,
             };
@@ -140,6 +152,185 @@ impl Source {
         sqlx::query_as!(Source, "SELECT s.* FROM songs_to_sources sts JOIN sources s ON s.id = sts.source_id WHERE sts.song_id = $1", song_id).fetch_all(executor).await.map_err(|e| Error::Select("songs_to_sources", e))
     }
 
+    /// Paths of every source in the storage backend
+    pub async fn paths_in_backend(
+        storage_backend_name: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<FxHashSet<String>, Error> {
+        sqlx::query_scalar!(
+            "SELECT path FROM sources WHERE storage_backend_name = $1",
+            storage_backend_name
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("sources", e))
+        .map(|paths| paths.into_iter().collect())
+    }
+
+    /// Every source in the storage backend that songs use, once each with the id of one of its songs
+    pub async fn for_songs_in_backend(
+        storage_backend_name: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<(i64, Self)>, Error> {
+        sqlx::query!(
+            r#"
+            SELECT sts.song_id AS "song_id!", s.* FROM sources s
+            JOIN songs_to_sources sts ON sts.source_id = s.id
+                AND sts.song_id = (
+                    SELECT MIN(o.song_id) FROM songs_to_sources o
+                    JOIN songs ON songs.id = o.song_id
+                    WHERE o.source_id = s.id
+                )
+            WHERE s.storage_backend_name = $1
+            ORDER BY sts.song_id
+            "#,
+            storage_backend_name
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("songs_to_sources", e))
+        .map(|rows| {
+            rows.into_iter()
+                .map(|row| {
+                    (
+                        row.song_id,
+                        Self {
+                            id: row.id,
+                            path: row.path,
+                            mime_type: row.mime_type,
+                            storage_backend_name: row.storage_backend_name,
+                            content_hash: row.content_hash,
+                            in_place: row.in_place,
+                            created_at: row.created_at,
+                            updated_at: row.updated_at,
+                        },
+                    )
+                })
+                .collect()
+        })
+    }
+
+    pub async fn set_path(
+        id: i64,
+        path: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!("UPDATE sources SET path = $1 WHERE id = $2", path, id)
+            .execute(executor)
+            .await
+            .map_err(|e| Error::Update("sources", e))
+            .map(|_| ())
+    }
+
+    /// A song whose source has the same content, and that source
+    pub async fn song_with_hash(
+        content_hash: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Option<(i64, Self)>, Error> {
+        sqlx::query!(
+            r#"
+            SELECT sts.song_id AS "song_id!", s.* FROM songs_to_sources sts
+            JOIN sources s ON s.id = sts.source_id
+            WHERE s.content_hash = $1
+            ORDER BY sts.song_id
+            LIMIT 1
+            "#,
+            content_hash
+        )
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| Error::Select("songs_to_sources", e))
+        .map(|row| {
+            row.map(|row| {
+                (
+                    row.song_id,
+                    Self {
+                        id: row.id,
+                        path: row.path,
+                        mime_type: row.mime_type,
+                        storage_backend_name: row.storage_backend_name,
+                        content_hash: row.content_hash,
+                        in_place: row.in_place,
+                        created_at: row.created_at,
+                        updated_at: row.updated_at,
+                    },
+                )
+            })
+        })
+    }
+
+    /// Sources that were added before content hashes, or whose hashing failed
+    pub async fn get_unhashed(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(Source, "SELECT * FROM sources WHERE content_hash IS NULL")
+            .fetch_all(executor)
+            .await
+            .map_err(|e| Error::Select("sources", e))
+    }
+
+    pub async fn set_content_hash(
+        id: i64,
+        content_hash: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            "UPDATE sources SET content_hash = $1 WHERE id = $2",
+            content_hash,
+            id
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Update("sources", e))
+        .map(|_| ())
+    }
+
+    /// How many sources have the same content as another source
+    pub async fn count_duplicates(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<i64, Error> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "count!" FROM sources s
+            WHERE s.content_hash IS NOT NULL AND EXISTS (
+                SELECT 1 FROM sources o WHERE o.content_hash = s.content_hash AND o.id < s.id
+            )
+            "#
+        )
+        .fetch_one(executor)
+        .await
+        .map_err(|e| Error::Select("sources", e))
+    }
+
+    /// Sources that no song, album or artist uses anymore
+    pub async fn get_orphaned(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(
+            Source,
+            r#"
+            SELECT s.* FROM sources s
+            WHERE NOT EXISTS (SELECT 1 FROM songs_to_sources sts WHERE sts.source_id = s.id)
+                AND NOT EXISTS (SELECT 1 FROM albums a WHERE a.cover_image_source_id = s.id)
+                AND NOT EXISTS (SELECT 1 FROM artists a WHERE a.image_source_id = s.id)
+            "#
+        )
+        .fetch_all(executor)
+        .await
+        .map_err(|e| Error::Select("sources", e))
+    }
+
+    pub async fn delete(
+        id: i64,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!("DELETE FROM sources WHERE id = $1", id)
+            .execute(executor)
+            .await
+            .map_err(|e| Error::Delete("sources", e))
+            .map(|_| ())
+    }
+
     pub async fn get_by_id_w_backend(
         id: i64,
         executor: impl Executor<'_, Database = super::DB>,
@@ -154,6 +345,8 @@ impl Source {
                 path: result.path,
                 mime_type: result.mime_type,
                 storage_backend_name: result.storage_backend_name,
+                content_hash: result.content_hash,
+                in_place: result.in_place,
                 created_at: result.created_at,
                 updated_at: result.updated_at,
             },
@@ -168,10 +361,10 @@ impl Source {
         // Fast path where we have an unexpired url in the cache already
         {
             let url_cache = REQUESTS_CACHE.read().await;
-            if let Some((inserted_at, req)) = url_cache.get(&self.id) {
-                if inserted_at.elapsed() <= REQ_VALID_FOR {
-                    return Ok(req.clone());
-                }
+            if let Some((inserted_at, req)) = url_cache.get(&self.id)
+                && inserted_at.elapsed() <= REQ_VALID_FOR
+            {
+                return Ok(req.clone());
             }
         }
 
@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 use sqlx::prelude::*;
 use tokio::sync::RwLock;
 
+use crate::storage_path::PathTemplate;
+
 use super::Error;
 
 /// Storage backend in the db
@@ -48,6 +50,11 @@ pub enum StorageBackendConfig {
 #[ts(export, export_to = "../web/src/types/FsConfig.ts")]
 pub struct FsConfig {
     pub root: Arc<str>,
+
+    /// Where new songs are written, see [`PathTemplate`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(as = "Option<String>", optional)]
+    pub path_template: Option<PathTemplate>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ts_rs::TS)]
@@ -60,6 +67,11 @@ pub struct S3Config {
     pub region: Arc<str>,
 
     pub bucket: Arc<str>,
+
+    /// Where new songs are written, see [`PathTemplate`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(as = "Option<String>", optional)]
+    pub path_template: Option<PathTemplate>,
 }
 
 impl DBStorageBackend {
@@ -75,6 +87,16 @@ impl DBStorageBackend {
 }
 
 impl StorageBackendConfig {
+    /// Template for the paths of songs, the default one if it isn't set
+    pub fn path_template(&self) -> PathTemplate {
+        match self {
+            StorageBackendConfig::Fs(FsConfig { path_template, .. })
+            | StorageBackendConfig::S3(S3Config { path_template, .. }) => {
+                path_template.clone().unwrap_or_default()
+            }
+        }
+    }
+
     pub fn operator(&self) -> Result<Operator, Error> {
         Ok(match self {
             StorageBackendConfig::Fs(fs_config) => {
@@ -51,4 +51,31 @@ impl Tag {
         .await
         .map_err(|e| Error::Select("songs_to_sources", e))
     }
+
+    /// Creates a plain tag, if there isn't one with this name already
+    pub async fn insert_or_ignore(
+        name: &str,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!("INSERT OR IGNORE INTO tags (name) VALUES ($1)", name)
+            .execute(executor)
+            .await
+            .map_err(|e| Error::Insert("tags", e))
+            .map(|_| ())
+    }
+
+    /// Splits a song's tags into its album (if any) and artists
+    pub fn album_and_artists(tags: &[Self]) -> (Option<String>, Vec<String>) {
+        let album = tags
+            .iter()
+            .find(|t| t.album_id.is_some())
+            .map(|t| t.name.clone());
+        let artists = tags
+            .iter()
+            .filter(|t| t.artist_id.is_some())
+            .map(|t| t.name.clone())
+            .collect();
+
+        (album, artists)
+    }
 }
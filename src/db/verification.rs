@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::*;
+
+use super::Error;
+
+/// Result of fully decoding a source, used to flag broken files
+#[derive(Debug, FromRow, Serialize, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/SourceVerification.ts")]
+#[serde(rename_all = "camelCase")]
+pub struct SourceVerification {
+    #[ts(type = "number")]
+    pub source_id: i64,
+
+    pub ok: bool,
+
+    /// Seconds of audio that actually decoded
+    pub duration: f64,
+
+    /// Seconds of audio the container claims to have
+    pub declared_duration: Option<f64>,
+
+    #[ts(type = "number")]
+    pub packets: i64,
+
+    #[ts(type = "number")]
+    pub lost_packets: i64,
+
+    pub error: Option<String>,
+
+    #[serde(skip_deserializing)]
+    pub created_at: chrono::NaiveDateTime,
+
+    #[serde(skip_deserializing)]
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+impl SourceVerification {
+    pub async fn get_all(
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<Vec<Self>, Error> {
+        sqlx::query_as!(SourceVerification, "SELECT * FROM source_verifications")
+            .fetch_all(executor)
+            .await
+            .map_err(|e| Error::Select("source_verifications", e))
+    }
+
+    /// Insert or replace the verification result for this source
+    pub async fn upsert(
+        &self,
+        executor: impl Executor<'_, Database = super::DB>,
+    ) -> Result<(), Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO source_verifications (source_id, ok, duration, declared_duration, packets, lost_packets, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (source_id) DO UPDATE SET
+                ok = excluded.ok,
+                duration = excluded.duration,
+                declared_duration = excluded.declared_duration,
+                packets = excluded.packets,
+                lost_packets = excluded.lost_packets,
+                error = excluded.error
+            "#,
+            self.source_id,
+            self.ok,
+            self.duration,
+            self.declared_duration,
+            self.packets,
+            self.lost_packets,
+            self.error
+        )
+        .execute(executor)
+        .await
+        .map_err(|e| Error::Insert("source_verifications", e))
+        .map(|_| ())
+    }
+}
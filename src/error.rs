@@ -1,10 +1,10 @@
 use std::io;
 
-use axum::response::IntoResponse;
+use axum::{Json, response::IntoResponse};
 use http::StatusCode;
 use thiserror::Error;
 
-use crate::db;
+use crate::{api::add_song::AddSongFailure, db};
 
 #[derive(Debug, Error)]
 pub enum ApiError {
@@ -24,12 +24,18 @@ pub enum ApiError {
     Axum(#[from] axum::Error),
     #[error("Invalid WS Message")]
     InvalidWSMessage,
+    #[error("Multipart Error: {0:?}")]
+    Multipart(#[from] axum::extract::multipart::MultipartError),
+    #[error("Bad Request: {0}")]
+    BadRequest(String),
     #[error("JSON Error: {0:?}")]
     SerdeJson(#[from] serde_json::Error),
     #[error("Symphonia Error: {0:?}")]
     Symphonia(#[from] symphonia::core::errors::Error),
     #[error("OpenDAL Error: {0:?}")]
     OpenDal(#[from] Box<opendal::Error>),
+    #[error("{0}")]
+    AddSong(Box<AddSongFailure>),
 }
 
 impl From<opendal::Error> for ApiError {
@@ -56,6 +62,11 @@ impl IntoResponse for ApiError {
             Self::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized").into_response(),
             Self::NotFound => (StatusCode::NOT_FOUND, "not found").into_response(),
             Self::SerdeJson(_) => (StatusCode::BAD_REQUEST, "invalid json").into_response(),
+            Self::Multipart(err) => (err.status(), err.body_text()).into_response(),
+            Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
+            Self::AddSong(failure) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, Json(failure)).into_response()
+            }
         }
     }
 }
@@ -0,0 +1,460 @@
+//! Chromaprint-style acoustic fingerprints. A song is decoded to mono at a low sample rate and
+//! split into overlapping frames, and the energy of each frame is folded into the 12 notes of
+//! the chromatic scale. Comparing those notes with each other and with earlier frames gives a
+//! 32 bit sub-fingerprint per frame that mostly survives re-encoding, so the same recording
+//! from YouTube and from a CD end up with fingerprints that are close in bit errors.
+
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use rustfft::{FftPlanner, num_complex::Complex};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{CODEC_TYPE_NULL, DecoderOptions},
+    errors::Error as SymphoniaError,
+    io::{MediaSource, MediaSourceStream},
+    probe::Hint,
+};
+
+/// Audio is downsampled to this first, the notes we look at are well below it
+const SAMPLE_RATE: f64 = 11025.0;
+const FRAME_SIZE: usize = 4096;
+/// Frames overlap by two thirds
+const FRAME_STEP: usize = FRAME_SIZE / 3;
+/// Only the start of a song is fingerprinted
+const MAX_SECONDS: f64 = 120.0;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+/// Chroma of this many frames is summed before comparing, to smooth over noise
+const SMOOTHING: usize = 3;
+/// Fingerprints are compared shifted by up to this many frames (about 10s), for songs with
+/// extra silence or an intro at the start
+const MAX_OFFSET: usize = 80;
+/// Shifted fingerprints have to still overlap by this many frames (about 5s), shorter songs
+/// are only compared as they are
+const MIN_OVERLAP: usize = 40;
+/// Songs whose durations are further apart than this many seconds aren't compared
+const DURATION_TOLERANCE: f64 = 10.0;
+/// How alike fingerprints have to be to count as the same song unless asked otherwise
+pub const DEFAULT_THRESHOLD: f32 = 0.8;
+
+#[derive(Debug)]
+pub struct Fingerprint {
+    pub hashes: Vec<u32>,
+    /// Seconds of audio in the whole song
+    pub duration: f64,
+}
+
+/// Decodes the start of the song and fingerprints it. Packets that fail to decode are skipped.
+pub fn fingerprint_audio(
+    song: Box<dyn MediaSource>,
+    mime_type: Option<&str>,
+) -> Result<Fingerprint, SymphoniaError> {
+    let src = MediaSourceStream::new(song, Default::default());
+    let mut hint = Hint::new();
+    if let Some(mime_type) = mime_type {
+        hint.mime_type(mime_type);
+    }
+
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        src,
+        &Default::default(),
+        &Default::default(),
+    )?;
+    let track = probed
+        .format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(SymphoniaError::Unsupported("no audio track"))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .filter(|rate| *rate != 0)
+        .ok_or(SymphoniaError::Unsupported("unknown sample rate"))?;
+    let declared_frames = track.codec_params.n_frames;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut resampler = Resampler::new(sample_rate);
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    let mut frames = 0u64;
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(err) => return Err(err),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_) | SymphoniaError::IoError(_)) => continue,
+            Err(err) => return Err(err),
+        };
+        frames += decoded.frames() as u64;
+        if resampler.is_full() {
+            // The rest only has to be decoded to know how long the song is
+            if declared_frames.is_some() {
+                break;
+            }
+            continue;
+        }
+
+        let channels = decoded.spec().channels.count().max(1);
+        let samples = samples
+            .get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+        if samples.capacity() < decoded.capacity() * channels {
+            *samples = SampleBuffer::new(decoded.capacity() as u64, *decoded.spec());
+        }
+        samples.copy_interleaved_ref(decoded);
+        for frame in samples.samples().chunks_exact(channels) {
+            resampler.push(frame.iter().sum::<f32>() / channels as f32);
+        }
+    }
+
+    let frames = declared_frames.unwrap_or(frames);
+    Ok(Fingerprint {
+        hashes: sub_fingerprints(&chroma(&resampler.out)),
+        duration: frames as f64 / sample_rate as f64,
+    })
+}
+
+/// Downsamples mono audio to [`SAMPLE_RATE`] by averaging the samples that fall into each
+/// output sample, which also filters out most of what's too high to keep
+struct Resampler {
+    step: f64,
+    pos: f64,
+    sum: f32,
+    count: u32,
+    out: Vec<f32>,
+}
+
+impl Resampler {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            step: SAMPLE_RATE / sample_rate as f64,
+            pos: 0.0,
+            sum: 0.0,
+            count: 0,
+            out: Vec::with_capacity((MAX_SECONDS * SAMPLE_RATE) as usize),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.out.len() >= (MAX_SECONDS * SAMPLE_RATE) as usize
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.sum += sample;
+        self.count += 1;
+        self.pos += self.step;
+        if self.pos < 1.0 {
+            return;
+        }
+
+        let avg = self.sum / self.count as f32;
+        while self.pos >= 1.0 {
+            self.out.push(avg);
+            self.pos -= 1.0;
+        }
+        self.sum = 0.0;
+        self.count = 0;
+    }
+}
+
+/// Energy of every note in each frame, normalized so loudness doesn't matter
+fn chroma(samples: &[f32]) -> Vec<[f32; 12]> {
+    let fft = FftPlanner::new().plan_fft_forward(FRAME_SIZE);
+    let window = (0..FRAME_SIZE)
+        .map(|i| {
+            0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FRAME_SIZE - 1) as f32).cos()
+        })
+        .collect::<Vec<_>>();
+    // Which note every frequency bin is closest to, if it's in the range we look at
+    let notes = (0..FRAME_SIZE / 2)
+        .map(|bin| {
+            let freq = bin as f64 * SAMPLE_RATE / FRAME_SIZE as f64;
+            (MIN_FREQ..MAX_FREQ).contains(&freq).then(|| {
+                let note = 12.0 * (freq / 440.0).log2() + 69.0;
+                (note.round() as i64).rem_euclid(12) as usize
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut buf = vec![Complex::default(); FRAME_SIZE];
+    samples
+        .windows(FRAME_SIZE)
+        .step_by(FRAME_STEP)
+        .map(|frame| {
+            for (c, (sample, w)) in buf.iter_mut().zip(frame.iter().zip(&window)) {
+                *c = Complex::new(sample * w, 0.0);
+            }
+            fft.process(&mut buf);
+
+            let mut chroma = [0f32; 12];
+            for (c, note) in buf.iter().zip(&notes) {
+                if let Some(note) = note {
+                    chroma[*note] += c.norm_sqr();
+                }
+            }
+            let norm = chroma.iter().map(|e| e * e).sum::<f32>().sqrt();
+            if norm > f32::EPSILON {
+                chroma.iter_mut().for_each(|e| *e /= norm);
+            }
+            chroma
+        })
+        .collect()
+}
+
+fn sub_fingerprints(chroma: &[[f32; 12]]) -> Vec<u32> {
+    let smoothed = chroma
+        .windows(SMOOTHING)
+        .map(|frames| {
+            let mut sum = [0f32; 12];
+            for frame in frames {
+                sum.iter_mut().zip(frame).for_each(|(s, e)| *s += e);
+            }
+            sum
+        })
+        .collect::<Vec<_>>();
+
+    smoothed
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let prev = &smoothed[i.saturating_sub(SMOOTHING)];
+            let mut hash = 0u32;
+            for note in 0..12 {
+                // The shape of the chroma
+                hash |= ((c[note] > c[(note + 1) % 12]) as u32) << note;
+                // How it changed since a few frames ago
+                hash |= ((c[note] > prev[note]) as u32) << (note + 12);
+            }
+            for note in 0..8 {
+                // Major thirds
+                hash |= ((c[note] > c[note + 4]) as u32) << (note + 24);
+            }
+            hash
+        })
+        .collect()
+}
+
+/// How alike two fingerprints are from 0 to 1 at the best offset between them. Unrelated songs
+/// are around 0.5 since about half the bits match by chance.
+pub fn similarity(a: &[u32], b: &[u32]) -> f32 {
+    let min_overlap = MIN_OVERLAP.min(a.len()).min(b.len()).max(1);
+    let mut best = 0.0f32;
+    for offset in 0..=MAX_OFFSET {
+        for (a, b) in [(a.get(offset..), Some(b)), (Some(a), b.get(offset..))] {
+            let (Some(a), Some(b)) = (a, b) else {
+                continue;
+            };
+            let overlap = a.len().min(b.len());
+            if overlap < min_overlap {
+                continue;
+            }
+
+            let errors = a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| (a ^ b).count_ones())
+                .sum::<u32>();
+            best = best.max(1.0 - errors as f32 / (overlap as f32 * 32.0));
+        }
+    }
+    best
+}
+
+/// Groups of songs with similar durations whose fingerprints are at least `threshold` alike,
+/// as indices into `songs` with the lowest similarity that joined the group. Songs that are
+/// alike through another song are in the same group.
+pub fn group_similar(songs: &[Fingerprint], threshold: f32) -> Vec<(Vec<usize>, f32)> {
+    let mut by_duration = (0..songs.len()).collect::<Vec<_>>();
+    by_duration.sort_by(|a, b| songs[*a].duration.total_cmp(&songs[*b].duration));
+
+    // Union-find of the songs, with the lowest similarity of each root's group
+    let mut parent = (0..songs.len()).collect::<Vec<_>>();
+    let mut lowest = vec![1.0f32; songs.len()];
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for (pos, &i) in by_duration.iter().enumerate() {
+        for &j in &by_duration[pos + 1..] {
+            if songs[j].duration - songs[i].duration > DURATION_TOLERANCE {
+                break;
+            }
+            let similarity = similarity(&songs[i].hashes, &songs[j].hashes);
+            if similarity < threshold {
+                continue;
+            }
+
+            let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+            let lowest_of_both = lowest[a].min(lowest[b]).min(similarity);
+            parent[b] = a;
+            lowest[a] = lowest_of_both;
+        }
+    }
+
+    let mut groups = FxHashMap::<usize, Vec<usize>>::default();
+    for i in 0..songs.len() {
+        groups.entry(root(&mut parent, i)).or_default().push(i);
+    }
+    groups
+        .into_iter()
+        .filter(|(_, group)| group.len() > 1)
+        .map(|(r, group)| (group, lowest[r]))
+        .sorted_by_key(|(group, _)| group[0])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random numbers, so the tests don't change between runs
+    fn lcg(seed: u64) -> impl FnMut() -> u32 {
+        let mut state = seed;
+        move || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 32) as u32
+        }
+    }
+
+    /// Random notes with an overtone, a new one every 0.4s, at [`SAMPLE_RATE`]
+    fn melody(seed: u64, seconds: f64) -> Vec<f32> {
+        let mut next = lcg(seed);
+        let note_len = (0.4 * SAMPLE_RATE) as usize;
+        let len = (seconds * SAMPLE_RATE) as usize;
+        let mut samples = Vec::with_capacity(len);
+        while samples.len() < len {
+            let freq = 440.0 * 2f64.powf((next() % 36) as f64 / 12.0 - 1.5);
+            samples.extend((0..note_len).map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * freq * i as f64 / SAMPLE_RATE;
+                (0.3 * phase.sin() + 0.15 * (2.0 * phase).sin()) as f32
+            }));
+        }
+        samples.truncate(len);
+        samples
+    }
+
+    fn fingerprint(samples: &[f32]) -> Vec<u32> {
+        sub_fingerprints(&chroma(samples))
+    }
+
+    fn song(hashes: &[u32], duration: f64) -> Fingerprint {
+        Fingerprint {
+            hashes: hashes.to_vec(),
+            duration,
+        }
+    }
+
+    #[test]
+    fn same_signal_is_alike() {
+        let samples = melody(1, 30.0);
+        let hashes = fingerprint(&samples);
+        assert_eq!(similarity(&hashes, &hashes), 1.0);
+
+        // Like a re-encode at another volume
+        let mut noise = lcg(2);
+        let copy = samples
+            .iter()
+            .map(|sample| 0.5 * sample + 0.02 * (noise() as f32 / u32::MAX as f32 - 0.5))
+            .collect::<Vec<_>>();
+        let alike = similarity(&hashes, &fingerprint(&copy));
+        assert!(alike > 0.95, "noisy copy was {alike} alike");
+    }
+
+    #[test]
+    fn shifted_signal_is_alike_within_max_offset() {
+        let samples = melody(3, 30.0);
+        let hashes = fingerprint(&samples);
+        let max_seconds = (MAX_OFFSET * FRAME_STEP) as f64 / SAMPLE_RATE;
+        for (seconds, matched) in [
+            (1.0, true),
+            (3.3, true),
+            (max_seconds - 0.5, true),
+            (max_seconds + 2.0, false),
+        ] {
+            let mut shifted = vec![0.0; (seconds * SAMPLE_RATE) as usize];
+            shifted.extend_from_slice(&samples);
+            let alike = similarity(&hashes, &fingerprint(&shifted));
+            // Both ways around, since either song can have the longer intro
+            assert_eq!(similarity(&fingerprint(&shifted), &hashes), alike);
+            assert_eq!(
+                alike >= DEFAULT_THRESHOLD,
+                matched,
+                "shifted by {seconds}s was {alike} alike"
+            );
+        }
+    }
+
+    #[test]
+    fn unrelated_signals_are_around_half_alike() {
+        let hashes = fingerprint(&melody(4, 30.0));
+        for seed in 5..10 {
+            let alike = similarity(&hashes, &fingerprint(&melody(seed, 30.0)));
+            assert!(
+                (0.4..0.7).contains(&alike),
+                "melody {seed} was {alike} alike"
+            );
+        }
+    }
+
+    #[test]
+    fn groups_are_transitive() {
+        let mut next = lcg(6);
+        let a = (0..100).map(|_| next()).collect::<Vec<_>>();
+        // 3 of 32 bits differ from a, then 3 other bits from b, so a and c are 6 bits apart
+        let b = a.iter().map(|hash| hash ^ 0b111).collect::<Vec<_>>();
+        let c = b.iter().map(|hash| hash ^ 0b111000).collect::<Vec<_>>();
+        let unrelated = (0..100).map(|_| next()).collect::<Vec<_>>();
+        assert!(similarity(&a, &c) < 0.85);
+
+        let songs = [
+            song(&a, 100.0),
+            song(&unrelated, 102.0),
+            song(&b, 104.0),
+            song(&c, 108.0),
+        ];
+        assert_eq!(
+            group_similar(&songs, 0.85),
+            [(vec![0, 2, 3], 1.0 - 3.0 / 32.0)]
+        );
+    }
+
+    #[test]
+    fn groups_need_similar_durations() {
+        let mut next = lcg(7);
+        let hashes = (0..100).map(|_| next()).collect::<Vec<_>>();
+
+        let close = [
+            song(&hashes, 100.0),
+            song(&hashes, 100.0 + DURATION_TOLERANCE - 1.0),
+        ];
+        assert_eq!(
+            group_similar(&close, DEFAULT_THRESHOLD),
+            [(vec![0, 1], 1.0)]
+        );
+
+        let apart = [
+            song(&hashes, 100.0),
+            song(&hashes, 100.0 + DURATION_TOLERANCE + 1.0),
+        ];
+        assert!(group_similar(&apart, DEFAULT_THRESHOLD).is_empty());
+    }
+}
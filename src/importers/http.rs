@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use axum::extract::ws::WebSocket;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{Semaphore, watch},
+};
+use tokio_util::task::AbortOnDropHandle;
+
+use crate::{
+    ApiError,
+    api::{
+        add_song::ALLOWED_MIME_TYPES,
+        audio::{UrlInitSongInfo, mime_type_from_extension},
+        upload::{MAX_UPLOAD_SIZE, StagedFile},
+    },
+    config::Config,
+    db::{
+        User,
+        provenance::{ImportMethod, SongProvenance},
+    },
+};
+
+use super::{ImportFuture, Imported, Importer, MetadataHints, PendingImport, forward_progress};
+
+/// How many files can download at the same time
+const MAX_CONCURRENT_DOWNLOADS: usize = 4;
+
+/// Downloads audio files straight from a http(s) url
+#[derive(Debug, Clone)]
+pub struct HttpImporter {
+    config: Arc<Config>,
+    client: reqwest::Client,
+    permits: Arc<Semaphore>,
+}
+
+struct PendingHttp {
+    info: UrlInitSongInfo,
+    username: String,
+    handle: AbortOnDropHandle<Result<StagedFile, ApiError>>,
+    progress: watch::Receiver<Option<f32>>,
+}
+
+impl HttpImporter {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            permits: Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS)),
+        }
+    }
+}
+
+impl Importer for HttpImporter {
+    type Descriptor = UrlInitSongInfo;
+
+    fn begin(&self, descriptor: &UrlInitSongInfo, user: &User) -> Box<dyn PendingImport> {
+        let (progress_tx, progress) = watch::channel(None);
+        let importer = self.clone();
+        let url = descriptor.url.clone();
+        let handle = tokio::spawn(async move {
+            let _permit = importer.permits.acquire().await.unwrap();
+            importer.download(&url, progress_tx).await
+        });
+
+        Box::new(PendingHttp {
+            info: descriptor.clone(),
+            username: user.username.clone(),
+            handle: AbortOnDropHandle::new(handle),
+            progress,
+        })
+    }
+}
+
+impl PendingImport for PendingHttp {
+    fn finish<'a>(self: Box<Self>, ws: &'a mut WebSocket) -> ImportFuture<'a> {
+        Box::pin(async move {
+            let PendingHttp {
+                info,
+                username,
+                mut handle,
+                mut progress,
+            } = *self;
+            let staged = forward_progress(ws, &mut handle, &mut progress).await?;
+
+            Ok(Imported {
+                staged,
+                provenance: SongProvenance::new(ImportMethod::Url, Some(&username), Some(&info.url)),
+                hints: MetadataHints {
+                    title: info.title,
+                    album: info.album,
+                    artists: info.artists,
+                    tags: Vec::new(),
+                },
+            })
+        })
+    }
+}
+
+impl HttpImporter {
+    async fn download(
+        &self,
+        url: &str,
+        progress: watch::Sender<Option<f32>>,
+    ) -> Result<StagedFile, ApiError> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|err| ApiError::BadRequest(format!("Invalid url {url}: {err}")))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(ApiError::BadRequest(format!(
+                "Can only import from http(s) urls, not {url}"
+            )));
+        }
+
+        let mut res = self.client.get(parsed).send().await?;
+        if !res.status().is_success() {
+            return Err(ApiError::BadRequest(format!(
+                "Couldn't download {url}: {}",
+                res.status()
+            )));
+        }
+        let Some(mime_type) = response_mime_type(&res) else {
+            return Err(ApiError::BadRequest(format!(
+                "{url} isn't an audio file we can import"
+            )));
+        };
+        let size = res.content_length();
+        if size.is_some_and(|size| size > MAX_UPLOAD_SIZE as u64) {
+            return Err(ApiError::BadRequest(format!("{url} is too big to import")));
+        }
+
+        let (staged, mut file) = StagedFile::create(&self.config.data_dir, mime_type).await?;
+        progress.send_replace(Some(0.0));
+        let written = async {
+            let mut written = 0u64;
+            while let Some(chunk) = res.chunk().await? {
+                written += chunk.len() as u64;
+                if written > MAX_UPLOAD_SIZE as u64 {
+                    return Err(ApiError::BadRequest(format!("{url} is too big to import")));
+                }
+                file.write_all(&chunk).await?;
+                if let Some(size) = size {
+                    progress.send_replace(Some(written as f32 / size as f32 * 100.0));
+                }
+            }
+            file.flush().await?;
+            Ok(())
+        }
+        .await;
+
+        match written {
+            Ok(()) => Ok(staged),
+            Err(err) => {
+                staged.remove().await;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Mime type from the response's headers, or the url's extension if they're too vague
+fn response_mime_type(res: &reqwest::Response) -> Option<Arc<str>> {
+    let from_header = res
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or_default().trim())
+        .filter(|mime_type| ALLOWED_MIME_TYPES.contains(mime_type));
+    let from_extension = || {
+        res.url()
+            .path_segments()?
+            .next_back()?
+            .rsplit_once('.')
+            .and_then(|(_, extension)| mime_type_from_extension(extension))
+    };
+
+    from_header.or_else(from_extension).map(Arc::from)
+}
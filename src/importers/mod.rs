@@ -0,0 +1,126 @@
+//! Sources songs can be imported from over the add songs WS. Each kind of [`InitSongInfo`]
+//! has an [`Importer`] that turns it into a staged file, so adding a source doesn't change
+//! how the WS goes through songs.
+
+mod http;
+mod upload;
+mod yt;
+
+use std::{future::Future, io, pin::Pin, sync::Arc};
+
+use axum::extract::ws::WebSocket;
+use tokio::sync::watch;
+
+use crate::{
+    ApiError,
+    api::{
+        audio::{InitSongInfo, ParsedMetadata},
+        protocol::ServerMessage,
+        upload::StagedFile,
+    },
+    config::Config,
+    db::{User, provenance::SongProvenance},
+    yt_dlp::YtDlp,
+};
+
+pub use http::HttpImporter;
+pub use upload::UploadImporter;
+pub use yt::YtImporter;
+
+/// A song's audio, staged and ready to be probed and added
+#[derive(Debug)]
+pub struct Imported {
+    pub staged: StagedFile,
+    pub hints: MetadataHints,
+    pub provenance: SongProvenance,
+}
+
+/// Metadata the source knows about, for whatever the file's own tags leave out
+#[derive(Debug, Default)]
+pub struct MetadataHints {
+    pub title: Option<Arc<str>>,
+    pub album: Option<Arc<str>>,
+    pub artists: Vec<Arc<str>>,
+    /// Added to the song once it's added
+    pub tags: Vec<Arc<str>>,
+}
+
+pub type ImportFuture<'a> = Pin<Box<dyn Future<Output = Result<Imported, ApiError>> + Send + 'a>>;
+
+/// Somewhere songs can be imported from
+pub trait Importer: Send + Sync {
+    /// What the client sends to import a song from here
+    type Descriptor;
+
+    /// Starts importing a song. Anything that doesn't need the client, like downloading,
+    /// should start right away so it's ready by the time the WS gets to the song.
+    fn begin(&self, descriptor: &Self::Descriptor, user: &User) -> Box<dyn PendingImport>;
+}
+
+/// An import that was started, finished one at a time since it may talk to the client
+pub trait PendingImport: Send {
+    fn finish<'a>(self: Box<Self>, ws: &'a mut WebSocket) -> ImportFuture<'a>;
+}
+
+/// Every importer, picked by the kind of song info the client sent
+#[derive(Debug, Clone)]
+pub struct Importers {
+    pub yt: YtImporter,
+    pub upload: UploadImporter,
+    pub http: HttpImporter,
+}
+
+impl MetadataHints {
+    /// Fills in what the file's tags didn't have
+    pub fn apply(&self, meta: &mut ParsedMetadata) {
+        if meta.title.is_none() {
+            meta.title = self.title.clone();
+        }
+        if meta.album.is_none() {
+            meta.album = self.album.clone();
+        }
+        if meta.artists.is_empty() {
+            meta.artists = self.artists.clone();
+        }
+    }
+}
+
+impl Importers {
+    pub fn new(config: Arc<Config>, yt_dlp: YtDlp) -> Self {
+        Self {
+            yt: YtImporter::new(yt_dlp),
+            upload: UploadImporter::new(config.clone()),
+            http: HttpImporter::new(config),
+        }
+    }
+
+    pub fn begin(&self, info: &InitSongInfo, user: &User) -> Box<dyn PendingImport> {
+        match info {
+            InitSongInfo::Yt(yt_init_song_info) => self.yt.begin(yt_init_song_info, user),
+            InitSongInfo::Uploaded(uploaded_init_song_info) => {
+                self.upload.begin(uploaded_init_song_info, user)
+            }
+            InitSongInfo::Url(url_init_song_info) => self.http.begin(url_init_song_info, user),
+        }
+    }
+}
+
+/// Forwards a background download's progress to the client until it's done
+async fn forward_progress<T>(
+    ws: &mut WebSocket,
+    handle: &mut tokio_util::task::AbortOnDropHandle<Result<T, ApiError>>,
+    progress: &mut watch::Receiver<Option<f32>>,
+) -> Result<T, ApiError> {
+    progress.mark_changed();
+
+    loop {
+        tokio::select! {
+            // Panicked or cancelled, which only fails this song
+            res = &mut *handle => return res.map_err(io::Error::from)?,
+            Ok(()) = progress.changed() => {
+                let progress = *progress.borrow_and_update();
+                ServerMessage::Progress(progress).send(ws).await?;
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::sync::Arc;
+
+use axum::extract::ws::WebSocket;
+
+use crate::{
+    ApiError,
+    api::{
+        audio::UploadedInitSongInfo,
+        protocol::{ServerMessage, UploadStatus},
+        upload::{ActiveUploads, StagedFile, Upload},
+    },
+    config::Config,
+    db::{
+        User,
+        provenance::{ImportMethod, SongProvenance},
+    },
+};
+
+use super::{ImportFuture, Imported, Importer, MetadataHints, PendingImport};
+
+/// Files the client sends in chunks over the WS
+#[derive(Debug, Clone)]
+pub struct UploadImporter {
+    config: Arc<Config>,
+    uploads: ActiveUploads,
+}
+
+struct PendingUpload {
+    config: Arc<Config>,
+    uploads: ActiveUploads,
+    info: UploadedInitSongInfo,
+    username: String,
+}
+
+impl UploadImporter {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            uploads: ActiveUploads::default(),
+        }
+    }
+}
+
+impl Importer for UploadImporter {
+    type Descriptor = UploadedInitSongInfo;
+
+    fn begin(&self, descriptor: &UploadedInitSongInfo, user: &User) -> Box<dyn PendingImport> {
+        Box::new(PendingUpload {
+            config: self.config.clone(),
+            uploads: self.uploads.clone(),
+            info: descriptor.clone(),
+            username: user.username.clone(),
+        })
+    }
+}
+
+impl PendingImport for PendingUpload {
+    fn finish<'a>(self: Box<Self>, ws: &'a mut WebSocket) -> ImportFuture<'a> {
+        Box::pin(async move {
+            let staged = receive_upload(ws, &self.config, &self.uploads, &self.info).await?;
+
+            Ok(Imported {
+                staged,
+                hints: MetadataHints::default(),
+                provenance: SongProvenance::new(
+                    ImportMethod::Ws,
+                    Some(&self.username),
+                    Some(&self.info.name),
+                ),
+            })
+        })
+    }
+}
+
+/// Receives a file as binary messages of an 8 byte big endian offset followed by the chunk data.
+/// We reply with the staged offset after every chunk, so a client that reconnects can send
+/// the upload id again and resume from where we left off.
+async fn receive_upload(
+    ws: &mut WebSocket,
+    config: &Config,
+    uploads: &ActiveUploads,
+    info: &UploadedInitSongInfo,
+) -> Result<StagedFile, ApiError> {
+    let data_dir = &config.data_dir;
+    let resumed = match &info.upload_id {
+        Some(upload_id) => {
+            // Only read how much we have once the old session stopped writing
+            let lease = uploads.lease(upload_id).await;
+            Upload::resume(data_dir, upload_id)
+                .await?
+                .filter(|upload| upload.size == info.size as u64)
+                .map(|upload| (upload, lease))
+        }
+        None => None,
+    };
+    let (mut upload, lease) = match resumed {
+        Some(resumed) => resumed,
+        None => {
+            let upload = Upload::create(data_dir, info.size as u64, info.mime_type.clone()).await?;
+            let lease = uploads.lease(&upload.id).await;
+            (upload, lease)
+        }
+    };
+
+    loop {
+        // Tells the client which upload it's sending and how many bytes we have staged
+        ServerMessage::Upload(UploadStatus {
+            upload_id: upload.id.clone(),
+            offset: upload.offset,
+            size: upload.size,
+        })
+        .send(ws)
+        .await?;
+
+        if upload.is_complete() {
+            return Ok(upload.into_staged());
+        }
+
+        let message = tokio::select! {
+            message = ws.recv() => message.ok_or(ApiError::InvalidWSMessage)??.into_data(),
+            () = lease.evicted() => {
+                return Err(ApiError::BadRequest(format!(
+                    "Upload {} was resumed by another session",
+                    upload.id
+                )));
+            }
+        };
+        let Some((offset, chunk)) = message.split_first_chunk::<8>() else {
+            return Err(ApiError::InvalidWSMessage);
+        };
+        upload
+            .write_chunk(u64::from_be_bytes(*offset), chunk)
+            .await?;
+    }
+}
@@ -0,0 +1,95 @@
+use axum::extract::ws::WebSocket;
+
+use crate::{
+    api::audio::YtInitSongInfo,
+    db::{
+        User,
+        provenance::{ImportMethod, SongProvenance},
+    },
+    yt_dlp::{YtDlp, YtDownload, YtDownloaded, YtVideoInfo},
+};
+
+use super::{ImportFuture, Imported, Importer, MetadataHints, PendingImport, forward_progress};
+
+/// Downloads videos with yt-dlp, which queues them so only a few run at once
+#[derive(Debug, Clone)]
+pub struct YtImporter {
+    yt_dlp: YtDlp,
+}
+
+struct PendingYt {
+    info: YtInitSongInfo,
+    username: String,
+    download: YtDownload,
+}
+
+impl YtImporter {
+    pub fn new(yt_dlp: YtDlp) -> Self {
+        Self { yt_dlp }
+    }
+}
+
+impl Importer for YtImporter {
+    type Descriptor = YtInitSongInfo;
+
+    fn begin(&self, descriptor: &YtInitSongInfo, user: &User) -> Box<dyn PendingImport> {
+        Box::new(PendingYt {
+            info: descriptor.clone(),
+            username: user.username.clone(),
+            download: self.yt_dlp.spawn(descriptor.clone()),
+        })
+    }
+}
+
+impl PendingImport for PendingYt {
+    fn finish<'a>(self: Box<Self>, ws: &'a mut WebSocket) -> ImportFuture<'a> {
+        Box::pin(async move {
+            let PendingYt {
+                info,
+                username,
+                download:
+                    YtDownload {
+                        mut handle,
+                        mut progress,
+                    },
+            } = *self;
+            let YtDownloaded {
+                staged,
+                info: video_info,
+            } = forward_progress(ws, &mut handle, &mut progress).await?;
+
+            Ok(Imported {
+                staged,
+                hints: MetadataHints {
+                    tags: info.tag.iter().cloned().collect(),
+                    ..Default::default()
+                },
+                provenance: provenance(&username, &info, video_info),
+            })
+        })
+    }
+}
+
+fn provenance(
+    username: &str,
+    yt_init_song_info: &YtInitSongInfo,
+    info: Option<YtVideoInfo>,
+) -> SongProvenance {
+    let mut provenance =
+        SongProvenance::new(ImportMethod::Yt, Some(username), Some(&yt_init_song_info.url));
+    provenance.video_id = yt_init_song_info.video_id.as_deref().map(str::to_string);
+    provenance.playlist_url = yt_init_song_info
+        .playlist_url
+        .as_deref()
+        .map(str::to_string);
+
+    if let Some(info) = info {
+        provenance.video_id = info.id.or(provenance.video_id);
+        provenance.channel = info.channel;
+        provenance.channel_url = info.channel_url;
+        provenance.upload_date = info.upload_date;
+        provenance.webpage_url = info.webpage_url;
+    }
+
+    provenance
+}
@@ -0,0 +1,38 @@
+use crate::{ApiError, db};
+
+use super::JobContext;
+
+/// Copies the database into `{data_dir}/backups` and removes the oldest backups past the limit
+pub async fn backup_database(ctx: &JobContext) -> Result<(), ApiError> {
+    let backups_dir = ctx.config.data_dir.join("backups");
+    tokio::fs::create_dir_all(&backups_dir).await?;
+
+    let backup_path = backups_dir.join(format!(
+        "db-{}.sqlite",
+        chrono::Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+    db::backup_to(&backup_path.to_string_lossy(), &ctx.sqlite).await?;
+    ctx.info(format!("Backed up database to {}", backup_path.display()))
+        .await;
+    ctx.result(backup_path.to_string_lossy(), true, None).await;
+
+    // Names sort by time, so the oldest come first
+    let mut backups = Vec::new();
+    let mut entries = tokio::fs::read_dir(&backups_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with("db-") && name.ends_with(".sqlite") {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort();
+
+    let to_remove = backups.len().saturating_sub(ctx.config.backups_to_keep);
+    for old_backup in &backups[..to_remove] {
+        tokio::fs::remove_file(old_backup).await?;
+        ctx.info(format!("Removed old backup {}", old_backup.display()))
+            .await;
+    }
+
+    Ok(())
+}
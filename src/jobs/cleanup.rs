@@ -0,0 +1,106 @@
+use std::{path::Path, time::SystemTime};
+
+use crate::{
+    ApiError,
+    api::upload::{PresignedUpload, uploads_dir},
+    db::{Source, StorageBackend},
+};
+
+use super::JobContext;
+
+/// Deletes sources (and their objects, unless a scan added them in place) that no song, album
+/// or artist uses anymore, and uploads that were never finished
+pub async fn cleanup_orphans(ctx: &JobContext) -> Result<(), ApiError> {
+    cleanup_stale_uploads(ctx).await?;
+
+    let orphans = Source::get_orphaned(&ctx.sqlite).await?;
+    ctx.set_total(orphans.len()).await;
+
+    for source in orphans {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        let item = format!("source {} ({})", source.id, source.path);
+        match StorageBackend::operator_by_name(&source.storage_backend_name, &ctx.sqlite).await? {
+            // The file is in the user's library, only the row is ours
+            Some(_) if source.in_place => {}
+            Some(operator) => {
+                if let Err(err) = operator.delete(&source.path).await {
+                    // Keep the row so we try again next time
+                    ctx.result(&item, false, Some(&format!("couldn't delete object: {err}")))
+                        .await;
+                    ctx.advance().await;
+                    continue;
+                }
+            }
+            None => ctx.warn(format!("No backend for {item}, only deleting row")).await,
+        }
+
+        Source::delete(source.id, &ctx.sqlite).await?;
+        ctx.result(&item, true, None).await;
+        ctx.advance().await;
+    }
+
+    Ok(())
+}
+
+async fn cleanup_stale_uploads(ctx: &JobContext) -> Result<(), ApiError> {
+    let mut entries = match tokio::fs::read_dir(uploads_dir(&ctx.config.data_dir)).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let modified = last_modified(&entry.path()).await?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age < ctx.config.upload_expiry {
+            continue;
+        }
+
+        let id = entry.file_name().to_string_lossy().into_owned();
+        let item = format!("upload {id}");
+        // Presigned uploads also have an object staged in their backend
+        if let Some(upload) = PresignedUpload::load(&ctx.config.data_dir, &id).await?
+            && let Some(operator) =
+                StorageBackend::operator_by_name(&upload.storage_backend, &ctx.sqlite).await?
+            && let Err(err) = operator.delete(&upload.key).await
+        {
+            ctx.result(
+                &item,
+                false,
+                Some(&format!("couldn't delete staged object: {err}")),
+            )
+            .await;
+            continue;
+        }
+
+        match tokio::fs::remove_dir_all(entry.path()).await {
+            Ok(()) => ctx.result(&item, true, None).await,
+            Err(err) => ctx.result(&item, false, Some(&format!("{err}"))).await,
+        }
+    }
+
+    Ok(())
+}
+
+/// When anything in an upload's dir last changed, appending chunks to `data` doesn't update
+/// the mtime of the dir itself
+async fn last_modified(dir: &Path) -> std::io::Result<SystemTime> {
+    let meta = tokio::fs::metadata(dir).await?;
+    let mut modified = meta.modified()?;
+    if !meta.is_dir() {
+        return Ok(modified);
+    }
+
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        modified = modified.max(entry.metadata().await?.modified()?);
+    }
+
+    Ok(modified)
+}
@@ -0,0 +1,102 @@
+use crate::{
+    ApiError,
+    api::{
+        add_song::default_storage_backend_name,
+        audio::{InitSongInfo, get_metadata},
+        media_source::ReaderMediaSource,
+    },
+    db::{Album, Song, Source, StorageBackend, song::SongWTags},
+    storage_path::extension_for_mime_type,
+};
+
+use super::JobContext;
+
+/// Finds a cover in the first song of every album and stores it as the album's cover
+pub async fn populate_album_covers(ctx: &JobContext) -> Result<(), ApiError> {
+    let albums = Album::get_all(&ctx.sqlite).await?;
+    let songs = Song::get_all_with_tags(true, &ctx.sqlite).await?;
+    ctx.set_total(albums.len()).await;
+
+    for album in albums {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        match populate_album_cover(ctx, &album, &songs).await {
+            Ok(true) => ctx.result(&album.title, true, None).await,
+            Ok(false) => {}
+            Err(err) => {
+                ctx.result(&album.title, false, Some(&format!("{err}")))
+                    .await
+            }
+        }
+        ctx.advance().await;
+    }
+
+    Ok(())
+}
+
+/// Returns false if there was no cover to populate
+async fn populate_album_cover(
+    ctx: &JobContext,
+    album: &Album,
+    songs: &[SongWTags],
+) -> Result<bool, ApiError> {
+    let Some(song) = songs.iter().find(|s| s.tags.contains(&album.title)) else {
+        tracing::debug!("No song for album {}", album.title);
+        return Ok(false);
+    };
+    let sources = Source::for_song(song.song.id, &ctx.sqlite).await?;
+    let Some(source) = sources.first().cloned() else {
+        tracing::debug!("song has no sources {}", song.song.title);
+        return Ok(false);
+    };
+    let Some(backend) =
+        StorageBackend::get_by_name(&source.storage_backend_name, &ctx.sqlite).await?
+    else {
+        return Ok(false);
+    };
+    let operator = backend.operator().await?;
+    let song = ReaderMediaSource::new(&operator, &source.path).await?;
+    let info = InitSongInfo::stored(&source.mime_type);
+    let meta = tokio::task::spawn_blocking(move || get_metadata(Box::new(song), &info, &[]))
+        .await
+        .unwrap()?;
+
+    let Some(album_cover) = meta.album_cover else {
+        tracing::debug!("No cover for {}", album.title);
+        return Ok(false);
+    };
+    // Scanned songs are in the user's library, so their album's cover goes to our default backend
+    let (cover_backend_name, cover_operator) = if source.in_place {
+        let name = default_storage_backend_name();
+        let Some(backend) = StorageBackend::get_by_name(&name, &ctx.sqlite).await? else {
+            return Ok(false);
+        };
+        (name.to_string(), backend.operator().await?)
+    } else {
+        (source.storage_backend_name, operator)
+    };
+    let cover_image_mime_type = &*album_cover.mime_type;
+    let cover_image_path = format!(
+        "images/{}.{}",
+        album.title.replace("/", "~slash~"),
+        extension_for_mime_type(cover_image_mime_type)
+    );
+    cover_operator
+        .write(&cover_image_path, album_cover.data)
+        .await?;
+    Album::insert_w_source_and_tag(
+        &album.title,
+        &cover_image_path,
+        cover_image_mime_type,
+        &cover_backend_name,
+        &ctx.sqlite,
+    )
+    .await?;
+
+    ctx.info(format!("Populated cover for {}", album.title))
+        .await;
+    Ok(true)
+}
@@ -0,0 +1,57 @@
+use crate::{
+    ApiError,
+    api::media_source::ReaderMediaSource,
+    db::{StorageBackend, fingerprint::SongFingerprint},
+    fingerprint::fingerprint_audio,
+};
+
+use super::JobContext;
+
+/// Fingerprints every song that doesn't have a fingerprint yet from its first audio source, so
+/// it can be found by the duplicate finder. Songs that fail are tried again next time.
+pub async fn fingerprint_songs(ctx: &JobContext) -> Result<(), ApiError> {
+    let songs = SongFingerprint::get_missing(&ctx.sqlite).await?;
+    ctx.set_total(songs.len()).await;
+
+    for (song_id, source) in songs {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        let item = format!("song {song_id} ({})", source.path);
+        let res = match StorageBackend::operator_by_name(&source.storage_backend_name, &ctx.sqlite)
+            .await?
+        {
+            Some(operator) => match ReaderMediaSource::new(&operator, &source.path).await {
+                Ok(song) => {
+                    let mime_type = source.mime_type.clone();
+                    tokio::task::spawn_blocking(move || {
+                        fingerprint_audio(Box::new(song), Some(&mime_type))
+                    })
+                    .await
+                    .unwrap()
+                    .map_err(|err| format!("couldn't decode audio: {err}"))
+                }
+                Err(err) => Err(format!("couldn't read from backend: {err}")),
+            },
+            None => Err("storage backend doesn't exist".to_string()),
+        };
+        match res {
+            Ok(fingerprint) => {
+                SongFingerprint::upsert(
+                    song_id,
+                    &fingerprint.hashes,
+                    fingerprint.duration,
+                    &ctx.sqlite,
+                )
+                .await?;
+                ctx.result(&item, true, None).await;
+            }
+            Err(err) => ctx.result(&item, false, Some(&err)).await,
+        }
+        ctx.advance().await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,47 @@
+use crate::{
+    ApiError, content_hash,
+    db::{Source, StorageBackend},
+};
+
+use super::JobContext;
+
+/// Hashes sources that were added before content hashes were stored, so they're found as
+/// duplicates too. Sources that can't be read are left unhashed and tried again next time.
+pub async fn hash_sources(ctx: &JobContext) -> Result<(), ApiError> {
+    let sources = Source::get_unhashed(&ctx.sqlite).await?;
+    ctx.set_total(sources.len()).await;
+
+    for source in sources {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        let item = format!("source {} ({})", source.id, source.path);
+        let res = match StorageBackend::operator_by_name(&source.storage_backend_name, &ctx.sqlite)
+            .await?
+        {
+            Some(operator) => content_hash::hash_object(&operator, &source.path)
+                .await
+                .map_err(|err| format!("couldn't read object: {err}")),
+            None => Err("storage backend doesn't exist".to_string()),
+        };
+        match res {
+            Ok(hash) => {
+                Source::set_content_hash(source.id, &hash, &ctx.sqlite).await?;
+                ctx.result(&item, true, None).await;
+            }
+            Err(err) => ctx.result(&item, false, Some(&err)).await,
+        }
+        ctx.advance().await;
+    }
+
+    let duplicates = Source::count_duplicates(&ctx.sqlite).await?;
+    if duplicates > 0 {
+        ctx.info(format!(
+            "{duplicates} sources have the same content as another source"
+        ))
+        .await;
+    }
+    Ok(())
+}
@@ -0,0 +1,277 @@
+use std::{
+    io,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI64, Ordering},
+    },
+};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    ApiError,
+    config::Config,
+    db::{
+        self,
+        job::{Job, JobLog, JobResult, JobStatus},
+    },
+};
+
+mod backup;
+mod cleanup;
+mod covers;
+mod fingerprint;
+mod hash_sources;
+mod relayout;
+mod rescan;
+mod scan;
+mod verify;
+mod watch;
+
+pub use relayout::RelayoutStorage;
+pub use scan::ScanLibrary;
+pub use watch::IngestWatchFolder;
+
+/// Every kind of background job and its parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "params", rename_all = "camelCase")]
+pub enum JobSpec {
+    PopulateAlbumCovers,
+    VerifySources,
+    CleanupOrphans,
+    BackupDatabase,
+    RescanMetadata,
+    ScanLibrary(ScanLibrary),
+    IngestWatchFolder(IngestWatchFolder),
+    RelayoutStorage(RelayoutStorage),
+    HashSources,
+    FingerprintSongs,
+}
+
+impl JobSpec {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobSpec::PopulateAlbumCovers => "populateAlbumCovers",
+            JobSpec::VerifySources => "verifySources",
+            JobSpec::CleanupOrphans => "cleanupOrphans",
+            JobSpec::BackupDatabase => "backupDatabase",
+            JobSpec::RescanMetadata => "rescanMetadata",
+            JobSpec::ScanLibrary(_) => "scanLibrary",
+            JobSpec::IngestWatchFolder(_) => "ingestWatchFolder",
+            JobSpec::RelayoutStorage(_) => "relayoutStorage",
+            JobSpec::HashSources => "hashSources",
+            JobSpec::FingerprintSongs => "fingerprintSongs",
+        }
+    }
+}
+
+/// Runs jobs in the background on a bounded number of workers
+#[derive(Debug, Clone)]
+pub struct Jobs {
+    inner: Arc<JobsInner>,
+}
+
+#[derive(Debug)]
+struct JobsInner {
+    config: Arc<Config>,
+    sqlite: Pool<Sqlite>,
+    workers: Semaphore,
+    /// Cancellation tokens for every job that's queued or running
+    active: Mutex<FxHashMap<i64, CancellationToken>>,
+}
+
+impl Jobs {
+    pub fn new(config: Arc<Config>, sqlite: Pool<Sqlite>) -> Self {
+        Self {
+            inner: Arc::new(JobsInner {
+                workers: Semaphore::new(config.job_workers.max(1)),
+                config,
+                sqlite,
+                active: Mutex::new(Default::default()),
+            }),
+        }
+    }
+
+    /// Queues the job and returns its id, it will start once a worker is free
+    pub async fn start(&self, spec: JobSpec, created_by: Option<&str>) -> Result<i64, db::Error> {
+        let (id, cancel) = self.insert(&spec, created_by).await?;
+        tokio::spawn(self.clone().run(id, spec, cancel));
+        Ok(id)
+    }
+
+    /// Queues the job and waits for it to finish, returns its id and how it finished
+    pub async fn run_to_completion(
+        &self,
+        spec: JobSpec,
+        created_by: Option<&str>,
+    ) -> Result<(i64, JobStatus, Option<String>), db::Error> {
+        let (id, cancel) = self.insert(&spec, created_by).await?;
+        let (status, error) = self.clone().run(id, spec, cancel).await;
+        Ok((id, status, error))
+    }
+
+    async fn insert(
+        &self,
+        spec: &JobSpec,
+        created_by: Option<&str>,
+    ) -> Result<(i64, CancellationToken), db::Error> {
+        let params = serde_json::to_string(spec).unwrap();
+        let id = Job::insert(spec.kind(), &params, created_by, &self.inner.sqlite).await?;
+
+        let cancel = CancellationToken::new();
+        self.inner.active.lock().unwrap().insert(id, cancel.clone());
+
+        Ok((id, cancel))
+    }
+
+    /// Returns false if the job isn't queued or running
+    pub fn cancel(&self, id: i64) -> bool {
+        match self.inner.active.lock().unwrap().get(&id) {
+            Some(cancel) => {
+                cancel.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn run(
+        self,
+        id: i64,
+        spec: JobSpec,
+        cancel: CancellationToken,
+    ) -> (JobStatus, Option<String>) {
+        let sqlite = &self.inner.sqlite;
+        let permit = tokio::select! {
+            permit = self.inner.workers.acquire() => permit.unwrap(),
+            _ = cancel.cancelled() => {
+                return self.finish(id, JobStatus::Cancelled, None).await;
+            }
+        };
+
+        if let Err(err) = Job::set_running(id, sqlite).await {
+            tracing::error!("Couldn't mark job {id} as running: {err:?}");
+        }
+        tracing::info!("Running job {id}: {spec:?}");
+
+        let ctx = Arc::new(JobContext {
+            id,
+            config: self.inner.config.clone(),
+            sqlite: sqlite.clone(),
+            cancel,
+            progress: AtomicI64::new(0),
+        });
+        // On its own task so a job that panics still gets marked as finished
+        let res = tokio::spawn(run_job(ctx.clone(), spec))
+            .await
+            .unwrap_or_else(|err| Err(io::Error::from(err).into()));
+        drop(permit);
+
+        match res {
+            Ok(()) if ctx.is_cancelled() => self.finish(id, JobStatus::Cancelled, None).await,
+            Ok(()) => self.finish(id, JobStatus::Succeeded, None).await,
+            Err(err) => {
+                ctx.error(format!("{err}")).await;
+                self.finish(id, JobStatus::Failed, Some(format!("{err}")))
+                    .await
+            }
+        }
+    }
+
+    async fn finish(
+        &self,
+        id: i64,
+        status: JobStatus,
+        error: Option<String>,
+    ) -> (JobStatus, Option<String>) {
+        tracing::info!("Job {id} finished: {status:?}");
+        self.inner.active.lock().unwrap().remove(&id);
+        if let Err(err) = Job::set_finished(id, status, error.as_deref(), &self.inner.sqlite).await
+        {
+            tracing::error!("Couldn't mark job {id} as finished: {err:?}");
+        }
+
+        (status, error)
+    }
+}
+
+async fn run_job(ctx: Arc<JobContext>, spec: JobSpec) -> Result<(), ApiError> {
+    match spec {
+        JobSpec::PopulateAlbumCovers => covers::populate_album_covers(&ctx).await,
+        JobSpec::VerifySources => verify::verify_sources(&ctx).await,
+        JobSpec::CleanupOrphans => cleanup::cleanup_orphans(&ctx).await,
+        JobSpec::BackupDatabase => backup::backup_database(&ctx).await,
+        JobSpec::RescanMetadata => rescan::rescan_metadata(&ctx).await,
+        JobSpec::ScanLibrary(params) => scan::scan_library(&ctx, &params).await,
+        JobSpec::IngestWatchFolder(params) => watch::ingest_watch_folder(&ctx, &params).await,
+        JobSpec::RelayoutStorage(params) => relayout::relayout_storage(&ctx, &params).await,
+        JobSpec::HashSources => hash_sources::hash_sources(&ctx).await,
+        JobSpec::FingerprintSongs => fingerprint::fingerprint_songs(&ctx).await,
+    }
+}
+
+/// Handed to running jobs so they can report progress, logs and results
+pub struct JobContext {
+    pub id: i64,
+    pub config: Arc<Config>,
+    pub sqlite: Pool<Sqlite>,
+    cancel: CancellationToken,
+    progress: AtomicI64,
+}
+
+impl JobContext {
+    /// Jobs should check this between items and stop early when it's true
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    pub async fn set_total(&self, total: usize) {
+        let progress = self.progress.load(Ordering::Relaxed);
+        if let Err(err) =
+            Job::set_progress(self.id, progress, Some(total as i64), &self.sqlite).await
+        {
+            tracing::error!("Couldn't set total for job {}: {err:?}", self.id);
+        }
+    }
+
+    /// Marks one more item as done
+    pub async fn advance(&self) {
+        let progress = self.progress.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Err(err) = Job::set_progress(self.id, progress, None, &self.sqlite).await {
+            tracing::error!("Couldn't set progress for job {}: {err:?}", self.id);
+        }
+    }
+
+    pub async fn info(&self, message: impl AsRef<str>) {
+        tracing::info!("job {}: {}", self.id, message.as_ref());
+        self.log("info", message.as_ref()).await;
+    }
+
+    pub async fn warn(&self, message: impl AsRef<str>) {
+        tracing::warn!("job {}: {}", self.id, message.as_ref());
+        self.log("warn", message.as_ref()).await;
+    }
+
+    pub async fn error(&self, message: impl AsRef<str>) {
+        tracing::error!("job {}: {}", self.id, message.as_ref());
+        self.log("error", message.as_ref()).await;
+    }
+
+    async fn log(&self, level: &str, message: &str) {
+        if let Err(err) = JobLog::insert(self.id, level, message, &self.sqlite).await {
+            tracing::error!("Couldn't write log for job {}: {err:?}", self.id);
+        }
+    }
+
+    /// Records the outcome for one item the job worked on
+    pub async fn result(&self, item: impl AsRef<str>, ok: bool, detail: Option<&str>) {
+        if let Err(err) = JobResult::insert(self.id, item.as_ref(), ok, detail, &self.sqlite).await
+        {
+            tracing::error!("Couldn't write result for job {}: {err:?}", self.id);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use opendal::Operator;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ApiError,
+    api::{
+        audio::{InitSongInfo, get_metadata},
+        media_source::ReaderMediaSource,
+        upload::move_object,
+    },
+    db::{Song, Source, StorageBackend, Tag},
+    storage_path::{PathFields, PathTemplate, unused_path},
+};
+
+use super::JobContext;
+
+/// Songs in a storage backend to move to where its path template puts them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayoutStorage {
+    pub storage_backend: String,
+}
+
+/// Moves every song's file to the path its current metadata renders to and updates its source.
+/// Songs that are already in the right place, or that a library scan added where they were,
+/// are left alone, so it's safe to run again.
+pub async fn relayout_storage(ctx: &JobContext, params: &RelayoutStorage) -> Result<(), ApiError> {
+    let backend = StorageBackend::get_by_name(&params.storage_backend, &ctx.sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let template = backend.config.path_template();
+    let operator = backend.operator().await?;
+    let sources = Source::for_songs_in_backend(&backend.name, &ctx.sqlite).await?;
+    ctx.set_total(sources.len()).await;
+
+    let mut unchanged = 0;
+    let mut in_place = 0;
+    for (song_id, source) in sources {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+        // Scanned files stay where the user put them
+        if source.in_place {
+            in_place += 1;
+            ctx.advance().await;
+            continue;
+        }
+
+        match relayout_source(ctx, &operator, &template, song_id, &source).await {
+            Ok(Some(path)) => {
+                ctx.result(&source.path, true, Some(&format!("Moved to {path}")))
+                    .await
+            }
+            Ok(None) => unchanged += 1,
+            Err(err) => {
+                ctx.result(&source.path, false, Some(&format!("{err}")))
+                    .await
+            }
+        }
+        ctx.advance().await;
+    }
+
+    if unchanged > 0 {
+        ctx.info(format!("{unchanged} songs were already in place"))
+            .await;
+    }
+    if in_place > 0 {
+        ctx.info(format!("{in_place} scanned songs were left where they are"))
+            .await;
+    }
+    Ok(())
+}
+
+/// Returns the new path if the file was moved
+async fn relayout_source(
+    ctx: &JobContext,
+    operator: &Operator,
+    template: &PathTemplate,
+    song_id: i64,
+    source: &Source,
+) -> Result<Option<String>, ApiError> {
+    let song = Song::get_by_id(song_id, &ctx.sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let tags = Tag::for_song(song_id, &ctx.sqlite).await?;
+    let (album, artists) = Tag::album_and_artists(&tags);
+
+    // Track numbers and the album artist are only in the file
+    let song_data = ReaderMediaSource::new(operator, &source.path).await?;
+    let info = InitSongInfo::stored(&source.mime_type);
+    let parsed = tokio::task::spawn_blocking(move || get_metadata(Box::new(song_data), &info, &[]))
+        .await
+        .unwrap()?;
+
+    let path = template.render(&PathFields {
+        title: &song.title,
+        album: album.as_deref(),
+        album_artist: parsed.album_artist.as_deref(),
+        artist: artists.first().map(|artist| &**artist),
+        disc: parsed.disc,
+        track: parsed.track,
+        mime_type: &source.mime_type,
+        timestamp: source.created_at.and_utc().timestamp(),
+    });
+    let path = unused_path(operator, path, Some(&source.path)).await?;
+    if path == source.path {
+        return Ok(None);
+    }
+
+    move_object(operator, &source.path, &path).await?;
+    if let Err(err) = Source::set_path(source.id, &path, &ctx.sqlite).await {
+        // Put it back so the source still points at it
+        if let Err(err) = move_object(operator, &path, &source.path).await {
+            tracing::error!("Couldn't move {path} back to {}: {err:?}", source.path);
+        }
+        return Err(err.into());
+    }
+
+    Ok(Some(path))
+}
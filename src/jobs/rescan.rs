@@ -0,0 +1,106 @@
+use crate::{
+    ApiError,
+    api::{
+        audio::{InitSongInfo, get_metadata},
+        media_source::ReaderMediaSource,
+    },
+    db::{Song, Source, StorageBackend, Tag, metadata_diff::MetadataDiff},
+};
+
+use super::JobContext;
+
+/// Re-reads tags from every song's file and stores a diff for songs whose metadata changed
+pub async fn rescan_metadata(ctx: &JobContext) -> Result<(), ApiError> {
+    let songs = Song::get_all(&ctx.sqlite).await?;
+    ctx.set_total(songs.len()).await;
+
+    for song in songs {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        match rescan_song(ctx, &song).await {
+            Ok(changed) => {
+                ctx.result(
+                    &song.title,
+                    true,
+                    changed.then_some("metadata differs from file"),
+                )
+                .await
+            }
+            Err(err) => {
+                ctx.result(&song.title, false, Some(&format!("{err}")))
+                    .await
+            }
+        }
+        ctx.advance().await;
+    }
+
+    Ok(())
+}
+
+/// Returns true if a diff was stored
+async fn rescan_song(ctx: &JobContext, song: &Song) -> Result<bool, ApiError> {
+    let Some(source) = Source::for_song(song.id, &ctx.sqlite)
+        .await?
+        .into_iter()
+        .next()
+    else {
+        return Ok(false);
+    };
+    let operator = StorageBackend::operator_by_name(&source.storage_backend_name, &ctx.sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let song_data = ReaderMediaSource::new(&operator, &source.path).await?;
+    let info = InitSongInfo::stored(&source.mime_type);
+    let parsed = tokio::task::spawn_blocking(move || get_metadata(Box::new(song_data), &info, &[]))
+        .await
+        .unwrap()?;
+
+    let tags = Tag::for_song(song.id, &ctx.sqlite).await?;
+    let (current_album, current_artists) = Tag::album_and_artists(&tags);
+
+    // Fields missing from the file keep their current value instead of being cleared
+    let title = parsed
+        .title
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+        .unwrap_or_else(|| song.title.clone());
+    let album = parsed
+        .album
+        .filter(|a| !a.is_empty())
+        .map(|a| a.to_string())
+        .or_else(|| current_album.clone());
+    let artists = if parsed.artists.is_empty() {
+        current_artists.clone()
+    } else {
+        parsed.artists.iter().map(|a| a.to_string()).collect()
+    };
+
+    let mut sorted_artists = artists.clone();
+    let mut sorted_current_artists = current_artists.clone();
+    sorted_artists.sort();
+    sorted_current_artists.sort();
+    if title == song.title && album == current_album && sorted_artists == sorted_current_artists {
+        MetadataDiff::delete(song.id, &ctx.sqlite).await?;
+        return Ok(false);
+    }
+
+    MetadataDiff {
+        song_id: song.id,
+        job_id: Some(ctx.id),
+        current_title: song.title.clone(),
+        current_album,
+        current_artists,
+        title,
+        album,
+        artists,
+        protected: song.metadata_edited,
+        created_at: chrono::Utc::now().naive_utc(),
+    }
+    .upsert(&ctx.sqlite)
+    .await?;
+
+    Ok(true)
+}
@@ -0,0 +1,193 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use opendal::{EntryMode, Operator};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ApiError,
+    api::{
+        add_song::{SongFile, add_song, final_metadata},
+        audio::{InitSongInfo, UploadedInitSongInfo, get_metadata, mime_type_from_extension},
+        media_source::ReaderMediaSource,
+    },
+    db::{
+        FsConfig, Source, StorageBackend, StorageBackendConfig,
+        job::Job,
+        provenance::{ImportMethod, SongProvenance},
+    },
+};
+
+use super::JobContext;
+
+/// Files already in a storage backend to add as songs where they are
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanLibrary {
+    /// Storage backend the files are in
+    pub storage_backend: String,
+    /// Only files under this path in the backend are scanned
+    #[serde(default)]
+    pub prefix: String,
+    /// Directory on the server's disk to scan instead. It's added as an fs storage backend
+    /// named `storage_backend` if there isn't one yet.
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+}
+
+/// Adds every audio file that isn't a source yet as a song, without copying it.
+/// Safe to run again, files that were added before are skipped.
+pub async fn scan_library(ctx: &JobContext, params: &ScanLibrary) -> Result<(), ApiError> {
+    if let Some(directory) = &params.directory {
+        directory_backend(ctx, &params.storage_backend, directory).await?;
+    }
+    let operator = StorageBackend::operator_by_name(&params.storage_backend, &ctx.sqlite)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let created_by = Job::get_by_id(ctx.id, &ctx.sqlite)
+        .await?
+        .and_then(|job| job.created_by);
+
+    let prefix = match params.prefix.trim_start_matches('/') {
+        "" => String::new(),
+        prefix if prefix.ends_with('/') => prefix.to_string(),
+        prefix => format!("{prefix}/"),
+    };
+    let known = Source::paths_in_backend(&params.storage_backend, &ctx.sqlite).await?;
+    let files = operator
+        .list_with(&prefix)
+        .recursive(true)
+        .await?
+        .into_iter()
+        .filter(|entry| entry.metadata().mode() == EntryMode::FILE)
+        .filter_map(|entry| {
+            let mime_type = Path::new(entry.path())
+                .extension()
+                .and_then(|ext| mime_type_from_extension(&ext.to_string_lossy()))?;
+            Some((entry.path().to_string(), mime_type))
+        })
+        .collect::<Vec<_>>();
+    ctx.set_total(files.len()).await;
+
+    let mut skipped = 0;
+    for (path, mime_type) in files {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        if known.contains(&path) {
+            skipped += 1;
+        } else {
+            match scan_file(
+                ctx,
+                &operator,
+                params,
+                &path,
+                mime_type,
+                created_by.as_deref(),
+            )
+            .await
+            {
+                Ok(song_id) => {
+                    ctx.result(&path, true, Some(&format!("Added as song {song_id}")))
+                        .await
+                }
+                Err(err) => ctx.result(&path, false, Some(&format!("{err}"))).await,
+            }
+        }
+        ctx.advance().await;
+    }
+
+    if skipped > 0 {
+        ctx.info(format!("Skipped {skipped} files that were already added"))
+            .await;
+    }
+    Ok(())
+}
+
+/// Makes sure there's an fs storage backend for the directory
+async fn directory_backend(ctx: &JobContext, name: &str, directory: &Path) -> Result<(), ApiError> {
+    let directory = tokio::fs::canonicalize(directory).await?;
+    let Some(backend) = StorageBackend::get_by_name(name, &ctx.sqlite).await? else {
+        let root = Arc::from(directory.to_string_lossy());
+        StorageBackend::try_insert_new(
+            name,
+            &StorageBackendConfig::Fs(FsConfig {
+                root,
+                path_template: None,
+            }),
+            &ctx.sqlite,
+        )
+        .await?;
+        ctx.info(format!(
+            "Added storage backend {name} for {}",
+            directory.display()
+        ))
+        .await;
+        return Ok(());
+    };
+
+    let root = match &backend.config {
+        StorageBackendConfig::Fs(FsConfig { root, .. }) => {
+            tokio::fs::canonicalize(&**root).await.ok()
+        }
+        StorageBackendConfig::S3(_) => None,
+    };
+    if root.as_ref() != Some(&directory) {
+        return Err(ApiError::BadRequest(format!(
+            "Storage backend {name} already exists for somewhere other than {}",
+            directory.display()
+        )));
+    }
+
+    Ok(())
+}
+
+async fn scan_file(
+    ctx: &JobContext,
+    operator: &Operator,
+    params: &ScanLibrary,
+    path: &str,
+    mime_type: &str,
+    created_by: Option<&str>,
+) -> Result<i64, ApiError> {
+    // Title falls back to the file's name when it has no tags
+    let name = Path::new(path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let info = InitSongInfo::Uploaded(UploadedInitSongInfo {
+        name: Arc::from(name),
+        size: 0,
+        mime_type: Arc::from(mime_type),
+        upload_id: None,
+        filename_template: None,
+    });
+    let song_data = ReaderMediaSource::new(operator, path).await?;
+    let templates = ctx.config.filename_templates.clone();
+    let parsed =
+        tokio::task::spawn_blocking(move || get_metadata(Box::new(song_data), &info, &templates))
+            .await
+            .unwrap()?;
+
+    let final_meta = final_metadata(
+        None,
+        None,
+        Vec::new(),
+        Arc::from(&*params.storage_backend),
+        &parsed,
+    )?;
+    let res = add_song(
+        &ctx.sqlite,
+        SongFile::InPlace { path, mime_type },
+        final_meta,
+        None,
+        &SongProvenance::new(ImportMethod::Scan, created_by, Some(path)),
+    )
+    .await?;
+
+    Ok(res.song_id)
+}
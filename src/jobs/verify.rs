@@ -0,0 +1,71 @@
+use crate::{
+    ApiError,
+    api::{
+        audio::{DecodeReport, verify_audio},
+        media_source::ReaderMediaSource,
+    },
+    db::{Source, StorageBackend},
+};
+
+use super::JobContext;
+
+/// Decodes every audio source and records which ones are broken
+pub async fn verify_sources(ctx: &JobContext) -> Result<(), ApiError> {
+    let sources = Source::get_all(&ctx.sqlite)
+        .await?
+        .into_iter()
+        .filter(|s| s.mime_type.starts_with("audio/"))
+        .collect::<Vec<_>>();
+    ctx.set_total(sources.len()).await;
+
+    for source in sources {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        let report = match StorageBackend::operator_by_name(&source.storage_backend_name, &ctx.sqlite)
+            .await?
+        {
+            Some(operator) => match ReaderMediaSource::new(&operator, &source.path).await {
+                Ok(song) => {
+                    let mime_type = source.mime_type.clone();
+                    tokio::task::spawn_blocking(move || {
+                        verify_audio(Box::new(song), Some(&mime_type))
+                    })
+                    .await
+                    .unwrap()
+                }
+                Err(err) => DecodeReport {
+                    fatal_error: Some(format!("couldn't read from backend: {err}")),
+                    ..Default::default()
+                },
+            },
+            None => DecodeReport {
+                fatal_error: Some("storage backend doesn't exist".into()),
+                ..Default::default()
+            },
+        };
+
+        let verification = report.into_verification(source.id);
+        verification.upsert(&ctx.sqlite).await?;
+        if !verification.ok {
+            ctx.warn(format!(
+                "Source {} ({}) is broken: {}",
+                source.id,
+                source.path,
+                verification.error.as_deref().unwrap_or_default()
+            ))
+            .await;
+        }
+        ctx.result(
+            format!("source {}", source.id),
+            verification.ok,
+            verification.error.as_deref(),
+        )
+        .await;
+        ctx.advance().await;
+    }
+
+    Ok(())
+}
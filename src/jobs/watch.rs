@@ -0,0 +1,164 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ApiError,
+    api::{
+        add_song::{SongFile, add_song, final_metadata, probe_staged},
+        audio::{InitSongInfo, UploadedInitSongInfo, mime_type_from_extension},
+        upload::StagedFile,
+    },
+    db::provenance::{ImportMethod, SongProvenance},
+    watcher::WatchFolder,
+};
+
+use super::JobContext;
+
+/// Files in the watch folder that stopped changing and are ready to import
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IngestWatchFolder {
+    pub files: Vec<PathBuf>,
+}
+
+/// Subdir of the watch folder files that couldn't be imported are moved to
+const FAILED_DIR: &str = "failed";
+
+/// Imports each file with its embedded metadata, then moves or deletes it
+pub async fn ingest_watch_folder(
+    ctx: &JobContext,
+    params: &IngestWatchFolder,
+) -> Result<(), ApiError> {
+    let Some(watch_folder) = &ctx.config.watch_folder else {
+        return Err(ApiError::BadRequest("No watch folder is configured".into()));
+    };
+    let watch_dir = tokio::fs::canonicalize(&watch_folder.path).await?;
+    ctx.set_total(params.files.len()).await;
+
+    for path in &params.files {
+        if ctx.is_cancelled() {
+            ctx.info("Cancelled").await;
+            break;
+        }
+
+        let item = path.display().to_string();
+        // Only ever touch files that are actually in the watch folder
+        let in_watch_dir = tokio::fs::canonicalize(path)
+            .await
+            .is_ok_and(|path| path.parent() == Some(&*watch_dir));
+        if !in_watch_dir {
+            ctx.result(&item, false, Some("Not in the watch folder"))
+                .await;
+            ctx.advance().await;
+            continue;
+        }
+
+        match ingest_file(ctx, watch_folder, path).await {
+            Ok(song_id) => {
+                let moved = match &watch_folder.move_to {
+                    Some(move_to) => move_into(path, move_to).await,
+                    None => tokio::fs::remove_file(path).await,
+                };
+                if let Err(err) = moved {
+                    ctx.warn(format!("Couldn't clean up {item}: {err}")).await;
+                }
+                ctx.result(&item, true, Some(&format!("Added as song {song_id}")))
+                    .await;
+            }
+            Err(err) => {
+                if let Err(err) = move_into(path, &watch_dir.join(FAILED_DIR)).await {
+                    ctx.warn(format!("Couldn't move {item} out of the way: {err}"))
+                        .await;
+                }
+                ctx.result(&item, false, Some(&format!("{err}"))).await;
+            }
+        }
+        ctx.advance().await;
+    }
+
+    Ok(())
+}
+
+async fn ingest_file(
+    ctx: &JobContext,
+    watch_folder: &WatchFolder,
+    path: &Path,
+) -> Result<i64, ApiError> {
+    let Some(mime_type) = path
+        .extension()
+        .and_then(|ext| mime_type_from_extension(&ext.to_string_lossy()))
+    else {
+        return Err(ApiError::BadRequest(
+            "Not an audio file we can import".into(),
+        ));
+    };
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // Title falls back to the file's name when it has no tags
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    let staged = StagedFile::external(path.to_path_buf(), Arc::from(mime_type));
+    let info = Arc::new(InitSongInfo::Uploaded(UploadedInitSongInfo {
+        name: Arc::from(stem),
+        size: tokio::fs::metadata(path).await?.len() as usize,
+        mime_type: staged.mime_type.clone(),
+        upload_id: None,
+        filename_template: None,
+    }));
+    let (parsed_meta, decode_report) =
+        probe_staged(&staged, info, ctx.config.filename_templates.clone()).await?;
+    if let Some(problem) = decode_report.problem() {
+        return Err(ApiError::BadRequest(format!(
+            "Corrupt audio file: {problem}"
+        )));
+    }
+
+    let final_meta = final_metadata(
+        None,
+        None,
+        Vec::new(),
+        watch_folder.storage_backend.clone(),
+        &parsed_meta,
+    )?;
+    let res = add_song(
+        &ctx.sqlite,
+        SongFile::Staged(&staged),
+        final_meta,
+        parsed_meta.album_cover,
+        &SongProvenance::new(ImportMethod::Watch, None, Some(&file_name)),
+    )
+    .await?;
+
+    Ok(res.song_id)
+}
+
+/// Moves the file into the dir, keeping its name unless there's already a file with it
+async fn move_into(path: &Path, dir: &Path) -> std::io::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    let file_name = path.file_name().unwrap_or_default();
+    let mut to = dir.join(file_name);
+    if tokio::fs::try_exists(&to).await? {
+        to = dir.join(format!(
+            "{}-{}",
+            chrono::Utc::now().timestamp(),
+            file_name.to_string_lossy()
+        ));
+    }
+
+    // Renaming doesn't work across filesystems
+    if tokio::fs::rename(path, &to).await.is_err() {
+        tokio::fs::copy(path, &to).await?;
+        tokio::fs::remove_file(path).await?;
+    }
+
+    Ok(())
+}
@@ -4,12 +4,14 @@ use api::api_router;
 use axum::{extract, Router};
 use color_eyre::eyre::{eyre, Context};
 use config::Config;
-use db::{StorageBackend, User};
+use db::{job::Job, StorageBackend, User};
 pub use error::ApiError;
 use http::{header::SET_COOKIE, HeaderValue, Method};
 use sqlx::sqlite::SqliteConnectOptions;
+use jobs::Jobs;
 use static_files::handle_static;
 use tokio::{net::TcpListener, signal};
+use yt_dlp::YtDlp;
 use tower_http::{
     cors::{AllowHeaders, CorsLayer},
     normalize_path::NormalizePathLayer,
@@ -18,9 +20,17 @@ use tower_http::{
 
 mod api;
 mod config;
+mod content_hash;
 mod db;
 mod error;
+mod fingerprint;
+mod importers;
+mod jobs;
+mod scheduler;
 mod static_files;
+mod storage_path;
+mod watcher;
+mod yt_dlp;
 
 fn main() -> color_eyre::Result<()> {
     let config = Config::from_json(&std::fs::read("./my-music-config.json")?)?;
@@ -69,10 +79,18 @@ async fn server(config: Config) -> color_eyre::Result<()> {
     StorageBackend::try_insert_new("init", &config.init_storage_backend, &sqlite).await?;
     StorageBackend::update_config("init", &config.init_storage_backend, &sqlite).await?;
     User::try_insert_new(&config.init_username, &config.init_password, true, &sqlite).await?;
+    let interrupted_jobs = Job::fail_unfinished(&sqlite).await?;
+    if interrupted_jobs > 0 {
+        tracing::warn!("Marked {interrupted_jobs} interrupted jobs as failed");
+    }
+    let jobs = Jobs::new(config.clone(), sqlite.clone());
+    scheduler::start(&config, &sqlite, &jobs).await?;
+    watcher::start(&config, &jobs);
 
     let base_path = config.domain.path().trim_end_matches("/");
 
-    let api_router = api_router(config.clone(), sqlite)?;
+    let yt_dlp = YtDlp::new(config.clone());
+    let api_router = api_router(config.clone(), sqlite, jobs, yt_dlp)?;
 
     let base_router = Router::<Arc<Config>>::new()
         .fallback({
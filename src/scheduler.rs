@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use color_eyre::eyre::eyre;
+use serde::Deserialize;
+use sqlx::{Pool, Sqlite};
+
+use crate::{
+    config::Config,
+    db::{self, scheduled_task::ScheduledTask},
+    jobs::{JobSpec, Jobs},
+};
+
+/// Maintenance tasks that can be run on a schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MaintenanceTask {
+    VerifySources,
+    CleanupOrphans,
+    BackupDatabase,
+    PopulateAlbumCovers,
+    FingerprintSongs,
+}
+
+/// Schedules can't run their task more often than this
+const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+pub struct Schedule {
+    pub task: MaintenanceTask,
+
+    /// i.e. "1day" or "12h"
+    #[serde(with = "humantime_serde")]
+    pub every: Duration,
+}
+
+impl Schedule {
+    /// Rejects schedules that would run back to back, or run the same task twice
+    pub fn check_all(schedules: &[Schedule]) -> color_eyre::Result<()> {
+        for (i, schedule) in schedules.iter().enumerate() {
+            let name = schedule.task.name();
+            if schedule.every < MIN_INTERVAL {
+                return Err(eyre!(
+                    "schedule for {name} must be at least {MIN_INTERVAL:?} apart, got {:?}",
+                    schedule.every
+                ));
+            }
+            if schedules[..i].iter().any(|other| other.task == schedule.task) {
+                return Err(eyre!("{name} is scheduled more than once"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MaintenanceTask {
+    pub fn job_spec(self) -> JobSpec {
+        match self {
+            MaintenanceTask::VerifySources => JobSpec::VerifySources,
+            MaintenanceTask::CleanupOrphans => JobSpec::CleanupOrphans,
+            MaintenanceTask::BackupDatabase => JobSpec::BackupDatabase,
+            MaintenanceTask::PopulateAlbumCovers => JobSpec::PopulateAlbumCovers,
+            MaintenanceTask::FingerprintSongs => JobSpec::FingerprintSongs,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        self.job_spec().kind()
+    }
+}
+
+/// Spawns a task for every configured schedule that runs its job forever
+pub async fn start(config: &Config, sqlite: &Pool<Sqlite>, jobs: &Jobs) -> Result<(), db::Error> {
+    let names = config
+        .schedules
+        .iter()
+        .map(|s| s.task.name())
+        .collect::<Vec<_>>();
+    ScheduledTask::delete_all_except(&names, sqlite).await?;
+
+    for schedule in &config.schedules {
+        ScheduledTask::upsert(
+            schedule.task.name(),
+            schedule.every.as_secs() as i64,
+            sqlite,
+        )
+        .await?;
+
+        let last_run_at = ScheduledTask::get_by_name(schedule.task.name(), sqlite)
+            .await?
+            .and_then(|task| task.last_run_at);
+        tokio::spawn(run_schedule(
+            schedule.task,
+            schedule.every,
+            last_run_at,
+            sqlite.clone(),
+            jobs.clone(),
+        ));
+    }
+
+    Ok(())
+}
+
+async fn run_schedule(
+    task: MaintenanceTask,
+    every: Duration,
+    last_run_at: Option<chrono::NaiveDateTime>,
+    sqlite: Pool<Sqlite>,
+    jobs: Jobs,
+) {
+    let every = chrono::Duration::from_std(every).unwrap_or(chrono::Duration::MAX);
+    // Pick up where we left off before a restart instead of running everything right away
+    let mut next_run_at = match last_run_at {
+        Some(at) => at.checked_add_signed(every),
+        None => Some(chrono::Utc::now().naive_utc()),
+    };
+
+    while let Some(run_at) = next_run_at {
+        if let Err(err) = ScheduledTask::set_next_run(task.name(), run_at, &sqlite).await {
+            tracing::error!("Couldn't record next run for {task:?}: {err:?}");
+        }
+
+        let wait = (run_at - chrono::Utc::now().naive_utc())
+            .to_std()
+            .unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        let ran_at = chrono::Utc::now().naive_utc();
+        tracing::info!("Running scheduled task {task:?}");
+        match jobs.run_to_completion(task.job_spec(), None).await {
+            Ok((job_id, status, error)) => {
+                if let Err(err) = ScheduledTask::record_run(
+                    task.name(),
+                    job_id,
+                    ran_at,
+                    status,
+                    error.as_deref(),
+                    &sqlite,
+                )
+                .await
+                {
+                    tracing::error!("Couldn't record run for {task:?}: {err:?}");
+                }
+            }
+            Err(err) => tracing::error!("Couldn't start scheduled task {task:?}: {err:?}"),
+        }
+
+        next_run_at = ran_at.checked_add_signed(every);
+    }
+
+    tracing::warn!("Interval for {task:?} is too long, it won't run again");
+}
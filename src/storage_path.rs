@@ -0,0 +1,238 @@
+use std::{str::FromStr, sync::Arc};
+
+use opendal::Operator;
+use serde::{Deserialize, Serialize};
+
+/// Where songs are written in a storage backend, like `{album_artist}/{album}/{disc}-{track} {title}.{ext}`.
+/// Every field is sanitised so it stays a single path component.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PathTemplate {
+    template: Arc<str>,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Album,
+    AlbumArtist,
+    Artist,
+    Disc,
+    Track,
+    Ext,
+    Timestamp,
+}
+
+/// What a song's path is made from
+#[derive(Debug, Clone, Copy)]
+pub struct PathFields<'a> {
+    pub title: &'a str,
+    pub album: Option<&'a str>,
+    /// Falls back to the first artist
+    pub album_artist: Option<&'a str>,
+    pub artist: Option<&'a str>,
+    pub disc: Option<u32>,
+    pub track: Option<u32>,
+    pub mime_type: &'a str,
+    /// When the song was added
+    pub timestamp: i64,
+}
+
+/// Paths songs were always written to, kept so existing setups don't change
+const DEFAULT_TEMPLATE: &str = "songs/{title}-{timestamp}.{ext}";
+/// Longest a single path component can get, most filesystems allow 255 bytes
+const MAX_COMPONENT_LEN: usize = 120;
+const UNKNOWN_ALBUM: &str = "Unknown Album";
+const UNKNOWN_ARTIST: &str = "Unknown Artist";
+
+impl Default for PathTemplate {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE.parse().unwrap()
+    }
+}
+
+impl PathTemplate {
+    pub fn render(&self, fields: &PathFields) -> String {
+        let mut path = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => path.push_str(literal),
+                Segment::Field(field) => path.push_str(&sanitise(&field.value(fields))),
+            }
+        }
+
+        // Empty literals between fields could leave empty components
+        path.split('/')
+            .filter(|component| !component.is_empty())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+impl Field {
+    fn value(self, fields: &PathFields) -> String {
+        match self {
+            Field::Title => fields.title.to_string(),
+            Field::Album => fields.album.unwrap_or(UNKNOWN_ALBUM).to_string(),
+            Field::AlbumArtist => fields
+                .album_artist
+                .or(fields.artist)
+                .unwrap_or(UNKNOWN_ARTIST)
+                .to_string(),
+            Field::Artist => fields.artist.unwrap_or(UNKNOWN_ARTIST).to_string(),
+            Field::Disc => fields.disc.unwrap_or(1).to_string(),
+            Field::Track => format!("{:02}", fields.track.unwrap_or(0)),
+            Field::Ext => extension_for_mime_type(fields.mime_type).to_string(),
+            Field::Timestamp => fields.timestamp.to_string(),
+        }
+    }
+}
+
+/// The extension files of the type usually have, instead of whatever the mime type's subtype is
+pub fn extension_for_mime_type(mime_type: &str) -> &str {
+    match mime_type {
+        "audio/mpeg" | "audio/mp3" => "mp3",
+        "audio/mp4" | "audio/x-m4a" | "audio/m4a" | "audio/aac" => "m4a",
+        "audio/ogg" | "audio/opus" => "ogg",
+        "audio/flac" | "audio/x-flac" => "flac",
+        "audio/wav" | "audio/x-wav" | "audio/wave" => "wav",
+        "audio/webm" => "webm",
+        _ => {
+            let subtype = mime_type.split_once('/').map_or(mime_type, |(_, sub)| sub);
+            subtype.strip_prefix("x-").unwrap_or(subtype)
+        }
+    }
+}
+
+/// Makes the value safe to use as one path component on any filesystem or bucket
+fn sanitise(value: &str) -> String {
+    let mut sanitised = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+
+    if sanitised.len() > MAX_COMPONENT_LEN {
+        let mut end = MAX_COMPONENT_LEN;
+        while !sanitised.is_char_boundary(end) {
+            end -= 1;
+        }
+        sanitised.truncate(end);
+    }
+
+    // Leading dots would hide the file, trailing dots and spaces get stripped by windows
+    let trimmed = sanitised
+        .trim_start_matches(['.', ' '])
+        .trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() {
+        "_".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Adds a number to the file's name until nothing is at the path yet, or only the file at `current`
+pub async fn unused_path(
+    operator: &Operator,
+    path: String,
+    current: Option<&str>,
+) -> Result<String, opendal::Error> {
+    let is_free = async |candidate: &str| {
+        Ok::<_, opendal::Error>(current == Some(candidate) || !operator.exists(candidate).await?)
+    };
+    if is_free(&path).await? {
+        return Ok(path);
+    }
+
+    let (stem, ext) = match path.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('/') => (stem, Some(ext)),
+        _ => (&*path, None),
+    };
+    for n in 2.. {
+        let candidate = match ext {
+            Some(ext) => format!("{stem} ({n}).{ext}"),
+            None => format!("{stem} ({n})"),
+        };
+        if is_free(&candidate).await? {
+            return Ok(candidate);
+        }
+    }
+    unreachable!()
+}
+
+impl FromStr for PathTemplate {
+    type Err = String;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = template.trim_start_matches('/');
+        while !rest.is_empty() {
+            let Some(start) = rest.find('{') else {
+                segments.push(Segment::Literal(rest.to_string()));
+                break;
+            };
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let Some(len) = rest[start..].find('}') else {
+                return Err(format!("Unclosed {{ in path template {template:?}"));
+            };
+            segments.push(Segment::Field(match &rest[start + 1..start + len] {
+                "title" => Field::Title,
+                "album" => Field::Album,
+                "album_artist" => Field::AlbumArtist,
+                "artist" => Field::Artist,
+                "disc" => Field::Disc,
+                "track" => Field::Track,
+                "ext" => Field::Ext,
+                "timestamp" => Field::Timestamp,
+                name => {
+                    return Err(format!(
+                        "Unknown field {{{name}}} in path template {template:?}"
+                    ));
+                }
+            }));
+            rest = &rest[start + len + 1..];
+        }
+
+        let escapes = segments.iter().any(|segment| match segment {
+            Segment::Literal(literal) => literal.split('/').any(|component| component == ".."),
+            Segment::Field(_) => false,
+        });
+        if escapes {
+            return Err(format!("Path template {template:?} can't use .."));
+        }
+        if !segments.contains(&Segment::Field(Field::Title)) {
+            return Err(format!("Path template {template:?} needs a {{title}}"));
+        }
+
+        Ok(Self {
+            template: Arc::from(template),
+            segments,
+        })
+    }
+}
+
+impl TryFrom<String> for PathTemplate {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        template.parse()
+    }
+}
+
+impl From<PathTemplate> for String {
+    fn from(template: PathTemplate) -> Self {
+        template.template.to_string()
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use rustc_hash::FxHashMap;
+use serde::Deserialize;
+
+use crate::{
+    api::{add_song::default_storage_backend_name, audio::mime_type_from_extension},
+    config::Config,
+    jobs::{IngestWatchFolder, JobSpec, Jobs},
+};
+
+/// Drop folder that songs are imported from automatically
+#[derive(Debug, Deserialize)]
+pub struct WatchFolder {
+    pub path: PathBuf,
+
+    /// How often to look for new files
+    #[serde(default = "default_poll_interval", with = "humantime_serde")]
+    pub poll_interval: Duration,
+
+    #[serde(default = "default_storage_backend_name")]
+    pub storage_backend: Arc<str>,
+
+    /// Imported files are moved here, or deleted if it isn't set.
+    /// Files that fail to import are moved to `{path}/failed`.
+    #[serde(default)]
+    pub move_to: Option<PathBuf>,
+}
+
+/// Size and modified time, a file is only imported once these stop changing
+type FileStat = (u64, Option<SystemTime>);
+
+fn default_poll_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
+/// Spawns a task that polls the watch folder forever, if there is one
+pub fn start(config: &Arc<Config>, jobs: &Jobs) {
+    if let Some(watch_folder) = &config.watch_folder {
+        tracing::info!("Watching {} for songs", watch_folder.path.display());
+        tokio::spawn(watch(config.clone(), jobs.clone()));
+    }
+}
+
+async fn watch(config: Arc<Config>, jobs: Jobs) {
+    let watch_folder = config.watch_folder.as_ref().unwrap();
+    let mut last_seen = FxHashMap::<PathBuf, FileStat>::default();
+    let mut interval = tokio::time::interval(watch_folder.poll_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let seen = match audio_files(&watch_folder.path).await {
+            Ok(seen) => seen,
+            Err(err) => {
+                tracing::error!(
+                    "Couldn't read watch folder {}: {err:?}",
+                    watch_folder.path.display()
+                );
+                continue;
+            }
+        };
+
+        // Files still being copied in will have changed since the last poll
+        let files = seen
+            .iter()
+            .filter(|(path, stat)| last_seen.get(*path) == Some(*stat))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        last_seen = seen;
+        if files.is_empty() {
+            continue;
+        }
+
+        let spec = JobSpec::IngestWatchFolder(IngestWatchFolder { files });
+        match jobs.run_to_completion(spec, None).await {
+            Ok((job_id, status, _)) => {
+                tracing::info!("Ingested watch folder in job {job_id}: {status:?}")
+            }
+            Err(err) => tracing::error!("Couldn't start watch folder ingest: {err:?}"),
+        }
+    }
+}
+
+/// Audio files directly in the folder, hidden ones are left alone since they're usually partial
+async fn audio_files(dir: &Path) -> std::io::Result<FxHashMap<PathBuf, FileStat>> {
+    let mut files = FxHashMap::default();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_audio = path
+            .extension()
+            .is_some_and(|ext| mime_type_from_extension(&ext.to_string_lossy()).is_some());
+        let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+        let metadata = entry.metadata().await?;
+        if is_audio && !is_hidden && metadata.is_file() {
+            files.insert(path, (metadata.len(), metadata.modified().ok()));
+        }
+    }
+
+    Ok(files)
+}
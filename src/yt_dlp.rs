@@ -0,0 +1,452 @@
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::Stdio,
+    sync::Arc,
+};
+
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    sync::{Semaphore, watch},
+};
+use tokio_util::task::AbortOnDropHandle;
+
+use crate::{
+    ApiError,
+    api::{
+        audio::{YtInitSongInfo, mime_type_from_extension},
+        upload::{StagedFile, new_id, uploads_dir},
+    },
+    config::Config,
+};
+
+/// Prefix of the progress lines we ask yt-dlp for, so they're easy to pick out of its output
+const PROGRESS_PREFIX: &str = "my-music-progress";
+/// Prefix of the line with the video's info as JSON, printed once it's downloaded
+const INFO_PREFIX: &str = "my-music-info";
+const OUTPUT_NAME: &str = "audio";
+const OUTPUT_TEMPLATE: &str = "audio.%(ext)s";
+/// Channels are playlists of tabs (videos, shorts, ...), which are playlists themselves
+const MAX_PLAYLIST_DEPTH: usize = 2;
+
+/// Queue that limits how many yt-dlp processes run at the same time
+#[derive(Debug, Clone)]
+pub struct YtDlp {
+    inner: Arc<YtDlpInner>,
+}
+
+#[derive(Debug)]
+struct YtDlpInner {
+    config: Arc<Config>,
+    permits: Semaphore,
+    /// Expanding playlists has its own permits, so it doesn't wait behind queued downloads
+    expand_permits: Semaphore,
+}
+
+/// A download that's queued or running, it's cancelled if this is dropped
+pub struct YtDownload {
+    pub handle: AbortOnDropHandle<Result<YtDownloaded, ApiError>>,
+    /// Percentage downloaded, None while waiting in the queue
+    pub progress: watch::Receiver<Option<f32>>,
+}
+
+#[derive(Debug)]
+pub struct YtDownloaded {
+    pub staged: StagedFile,
+    /// None if yt-dlp didn't print it for some reason
+    pub info: Option<YtVideoInfo>,
+}
+
+/// Fields from yt-dlp's info JSON that we keep around
+#[derive(Debug, Deserialize)]
+pub struct YtVideoInfo {
+    pub id: Option<String>,
+    pub channel: Option<String>,
+    pub channel_url: Option<String>,
+    pub upload_date: Option<String>,
+    pub webpage_url: Option<String>,
+    /// Where the final file ended up
+    pub filepath: Option<PathBuf>,
+}
+
+/// How yt-dlp downloads audio, set in config with per-import overrides
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct YtDlpOptions {
+    /// i.e. "opus", "m4a" or "best" to keep whatever the original is
+    pub audio_format: String,
+    /// 0 (best) to 10 (worst) for VBR, or a bitrate like "128K"
+    pub audio_quality: String,
+    pub embed_metadata: bool,
+    pub embed_thumbnail: bool,
+    /// Passed to yt-dlp as is. Only settable in config, since yt-dlp can run commands.
+    pub extra_args: Vec<String>,
+}
+
+/// Per-import changes to [`YtDlpOptions`]
+#[derive(Debug, Clone, Default, Deserialize, ts_rs::TS)]
+#[ts(export, export_to = "../web/src/types/YtDlpOverrides.ts")]
+#[serde(default, rename_all = "camelCase")]
+pub struct YtDlpOverrides {
+    #[ts(optional)]
+    pub audio_format: Option<String>,
+    #[ts(optional)]
+    pub audio_quality: Option<String>,
+    #[ts(optional)]
+    pub embed_metadata: Option<bool>,
+    #[ts(optional)]
+    pub embed_thumbnail: Option<bool>,
+}
+
+impl Default for YtDlpOptions {
+    fn default() -> Self {
+        Self {
+            audio_format: "opus".into(),
+            audio_quality: "0".into(),
+            embed_metadata: true,
+            embed_thumbnail: true,
+            extra_args: Vec::new(),
+        }
+    }
+}
+
+impl YtDlpOptions {
+    pub fn with_overrides(&self, overrides: &YtDlpOverrides) -> Self {
+        Self {
+            audio_format: overrides
+                .audio_format
+                .clone()
+                .unwrap_or_else(|| self.audio_format.clone()),
+            audio_quality: overrides
+                .audio_quality
+                .clone()
+                .unwrap_or_else(|| self.audio_quality.clone()),
+            embed_metadata: overrides.embed_metadata.unwrap_or(self.embed_metadata),
+            embed_thumbnail: overrides.embed_thumbnail.unwrap_or(self.embed_thumbnail),
+            extra_args: self.extra_args.clone(),
+        }
+    }
+}
+
+/// What a url expanded to, a single video is a "playlist" of one without a title
+#[derive(Debug)]
+pub struct YtPlaylist {
+    pub title: Option<Arc<str>>,
+    pub entries: Vec<YtEntry>,
+}
+
+#[derive(Debug)]
+pub struct YtEntry {
+    pub id: Arc<str>,
+    pub url: Arc<str>,
+}
+
+/// The parts of `yt-dlp -J --flat-playlist` output we care about
+#[derive(Debug, Deserialize)]
+struct FlatInfo {
+    id: Option<String>,
+    title: Option<String>,
+    url: Option<String>,
+    webpage_url: Option<String>,
+    #[serde(rename = "_type")]
+    kind: Option<String>,
+    ie_key: Option<String>,
+    #[serde(default)]
+    entries: Vec<Option<FlatInfo>>,
+}
+
+impl FlatInfo {
+    fn is_playlist(&self) -> bool {
+        self.kind.as_deref() == Some("playlist") || self.ie_key.as_deref() == Some("YoutubeTab")
+    }
+}
+
+impl YtDlp {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            inner: Arc::new(YtDlpInner {
+                permits: Semaphore::new(config.yt_dlp_concurrency.max(1)),
+                expand_permits: Semaphore::new(config.yt_dlp_concurrency.max(1)),
+                config,
+            }),
+        }
+    }
+
+    /// Queues a download and returns right away
+    pub fn spawn(&self, info: YtInitSongInfo) -> YtDownload {
+        let (progress_tx, progress) = watch::channel(None);
+        let inner = self.inner.clone();
+        let handle = tokio::spawn(async move {
+            let _permit = inner.permits.acquire().await.unwrap();
+            inner.download(&info, progress_tx).await
+        });
+
+        YtDownload {
+            handle: AbortOnDropHandle::new(handle),
+            progress,
+        }
+    }
+}
+
+impl YtDlp {
+    /// Expands a playlist or channel url into its videos, in order
+    pub async fn expand(&self, url: &str) -> Result<YtPlaylist, ApiError> {
+        let _permit = self.inner.expand_permits.acquire().await.unwrap();
+        let info = self.inner.flat_info(url).await?;
+        let mut playlist = YtPlaylist {
+            title: info
+                .is_playlist()
+                .then(|| info.title.as_deref().map(Arc::from))
+                .flatten(),
+            entries: Vec::new(),
+        };
+        self.inner
+            .collect_entries(url, info, 0, &mut playlist.entries)
+            .await?;
+
+        Ok(playlist)
+    }
+}
+
+/// Whether a url is a playlist or channel that has to be expanded into its videos
+pub fn is_collection_url(url: &str) -> bool {
+    ["list=", "/playlist", "/channel/", "/@"]
+        .iter()
+        .any(|pattern| url.contains(pattern))
+}
+
+/// The id of the video a url points to, if it's one of YouTube's usual video urls
+pub fn video_id_from_url(url: &str) -> Option<Arc<str>> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    let mut segments = parsed.path_segments()?;
+    let id = match (parsed.host_str()?, segments.next()?) {
+        ("youtu.be", id) => Some(id.to_owned()),
+        (_, "watch") => parsed
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, id)| id.into_owned()),
+        (_, "shorts" | "live") => segments.next().map(str::to_owned),
+        _ => None,
+    }?;
+
+    (!id.is_empty()).then(|| Arc::from(id))
+}
+
+impl YtDlpInner {
+    async fn flat_info(&self, url: &str) -> Result<FlatInfo, ApiError> {
+        let cookies_path = &*self.config.yt_dlp_cookies_path;
+        let mut command = tokio::process::Command::new("yt-dlp");
+        command.args(["--flat-playlist", "-J"]).kill_on_drop(true);
+        if tokio::fs::try_exists(cookies_path).await? {
+            command.args(["--cookies", cookies_path]);
+        }
+
+        let output = command.arg(url).output().await?;
+        if !output.status.success() {
+            tracing::error!(
+                "Failed to get info for \"{url}\": {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return Err(ApiError::BadRequest(format!(
+                "yt-dlp couldn't get info for {url}"
+            )));
+        }
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    fn collect_entries<'a>(
+        &'a self,
+        url: &'a str,
+        info: FlatInfo,
+        depth: usize,
+        entries: &'a mut Vec<YtEntry>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), ApiError>> + Send + 'a>> {
+        Box::pin(async move {
+            if !info.is_playlist() {
+                // Only the url we were given is known to point at this video
+                let video_url = info
+                    .webpage_url
+                    .or(info.url)
+                    .or_else(|| (depth == 0).then(|| url.to_string()));
+                match (info.id, video_url) {
+                    (Some(id), Some(video_url)) => entries.push(YtEntry {
+                        id: Arc::from(id),
+                        url: Arc::from(video_url),
+                    }),
+                    _ => tracing::warn!("Skipping entry of {url} without an id or url"),
+                }
+                return Ok(());
+            }
+
+            if depth >= MAX_PLAYLIST_DEPTH {
+                tracing::warn!("Not expanding nested playlist {url}, it's too deep");
+                return Ok(());
+            }
+
+            for entry in info.entries.into_iter().flatten() {
+                // Flat entries of nested playlists only have a url, so get those separately
+                let nested_url = entry.url.clone();
+                match nested_url {
+                    Some(nested_url) if entry.is_playlist() && entry.entries.is_empty() => {
+                        let nested = self.flat_info(&nested_url).await?;
+                        self.collect_entries(&nested_url, nested, depth + 1, entries)
+                            .await?;
+                    }
+                    _ => self.collect_entries(url, entry, depth + 1, entries).await?,
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Downloads into its own dir under `{data_dir}/uploads`, so stale ones get cleaned up with uploads
+    async fn download(
+        &self,
+        info: &YtInitSongInfo,
+        progress: watch::Sender<Option<f32>>,
+    ) -> Result<YtDownloaded, ApiError> {
+        tracing::debug!("using yt-dlp for {info:?}");
+        let dir = uploads_dir(&self.config.data_dir).join(&*new_id());
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let res = self.run(info, &dir, progress).await;
+        if res.is_err()
+            && let Err(err) = tokio::fs::remove_dir_all(&dir).await
+        {
+            tracing::error!("Couldn't remove staging dir {}: {err:?}", dir.display());
+        }
+        res
+    }
+
+    async fn run(
+        &self,
+        info: &YtInitSongInfo,
+        dir: &Path,
+        progress: watch::Sender<Option<f32>>,
+    ) -> Result<YtDownloaded, ApiError> {
+        let options = self.config.yt_dlp.with_overrides(&info.options);
+        let cookies_path = &*self.config.yt_dlp_cookies_path;
+        let has_cookies_file = tokio::fs::try_exists(cookies_path).await?;
+        let mut command = tokio::process::Command::new("yt-dlp");
+        command.args([
+            "-x",
+            "--audio-format",
+            &options.audio_format,
+            "--audio-quality",
+            &options.audio_quality,
+        ]);
+        if options.embed_metadata {
+            command.arg("--embed-metadata");
+        }
+        if options.embed_thumbnail {
+            command.arg("--embed-thumbnail");
+        }
+        command
+            .args(&options.extra_args)
+            .args([
+                "--sleep-requests",
+                "0.75",
+                "--newline",
+                "--progress-template",
+                &format!(
+                    "download:{PROGRESS_PREFIX} %(progress.downloaded_bytes)s %(progress.total_bytes,total_bytes_estimate)s"
+                ),
+                // --print makes it quiet, but we still want progress
+                "--progress",
+                "--print",
+                &format!(
+                    "after_move:{INFO_PREFIX} %(.{{id,channel,channel_url,upload_date,webpage_url,filepath}})j"
+                ),
+                "-o",
+            ])
+            // Extension depends on the format we end up with
+            .arg(dir.join(OUTPUT_TEMPLATE))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        if has_cookies_file {
+            command.args(["--cookies", cookies_path]);
+        }
+
+        let mut child = command.arg(&*info.url).spawn()?;
+        progress.send_replace(Some(0.0));
+
+        // Drain stderr alongside stdout so yt-dlp never blocks on a full pipe
+        let mut stderr = child.stderr.take().unwrap();
+        let stderr_task = tokio::spawn(async move {
+            let mut buf = String::new();
+            stderr.read_to_string(&mut buf).await.map(|_| buf)
+        });
+
+        let mut video_info: Option<YtVideoInfo> = None;
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        while let Some(line) = lines.next_line().await? {
+            if let Some(percent) = parse_progress(&line) {
+                progress.send_replace(Some(percent));
+            } else if let Some(info_json) = line.strip_prefix(INFO_PREFIX) {
+                video_info = serde_json::from_str(info_json.trim())
+                    .inspect_err(|err| tracing::warn!("Couldn't parse yt-dlp info: {err:?}"))
+                    .ok();
+            }
+        }
+
+        let status = child.wait().await?;
+        let stderr = stderr_task.await.unwrap().unwrap_or_default();
+        if !status.success() {
+            tracing::error!("Failed to download audio for \"{}\": {stderr}", info.url);
+            return Err(ApiError::BadRequest(format!(
+                "yt-dlp couldn't download {}",
+                info.url
+            )));
+        }
+
+        let path = match video_info
+            .as_ref()
+            .and_then(|info| info.filepath.clone())
+            .filter(|path| path.starts_with(dir))
+        {
+            Some(path) => path,
+            None => find_output(dir).await?,
+        };
+        let Some(mime_type) = path
+            .extension()
+            .and_then(|ext| mime_type_from_extension(&ext.to_string_lossy()))
+        else {
+            return Err(ApiError::BadRequest(format!(
+                "yt-dlp downloaded {} in a format we can't use",
+                info.url
+            )));
+        };
+
+        Ok(YtDownloaded {
+            staged: StagedFile::owned(path, dir.to_path_buf(), Arc::from(mime_type)),
+            info: video_info,
+        })
+    }
+}
+
+/// Finds the file yt-dlp downloaded, for when it didn't tell us where it is
+async fn find_output(dir: &Path) -> Result<PathBuf, ApiError> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.file_stem().is_some_and(|stem| stem == OUTPUT_NAME) {
+            return Ok(path);
+        }
+    }
+
+    Err(ApiError::NotFound)
+}
+
+/// Turns a line from our progress template into a percentage
+fn parse_progress(line: &str) -> Option<f32> {
+    let mut parts = line.strip_prefix(PROGRESS_PREFIX)?.split_whitespace();
+    let downloaded = parts.next()?.parse::<f64>().ok()?;
+    let total = parts.next()?.parse::<f64>().ok()?;
+    (total > 0.0).then(|| (downloaded / total * 100.0).min(100.0) as f32)
+}
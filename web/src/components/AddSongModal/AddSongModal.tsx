@@ -1,10 +1,12 @@
 import {
 	Button,
 	Center,
+	Checkbox,
 	Group,
 	List,
 	Loader,
 	LoadingOverlay,
+	Progress,
 	Stack,
 	Stepper,
 	Text,
@@ -23,14 +25,24 @@ import { preload } from 'swr';
 import { apiFetcher } from '../../api';
 import { closeAllModals } from '@mantine/modals';
 
-const ACCEPTED_MIME_TYPES = ['audio/*'];
+// Whole albums can be uploaded as a zip or tar
+const ACCEPTED_MIME_TYPES = [
+	'audio/*',
+	'application/zip',
+	'application/x-zip-compressed',
+	'application/x-tar',
+];
 
 export const AddSongModal = () => {
 	const { user } = useAuth({ admin: true });
 	const [files, setFiles] = useState<FileWithPath[]>([]);
 	const [ytUrl, setYtUrl] = useState<string>('');
+	const [playlistAsTag, setPlaylistAsTag] = useState(false);
+	const [keepOriginalFormat, setKeepOriginalFormat] = useState(false);
+	const [filenameTemplate, setFilenameTemplate] = useState('');
 	const [metadata, setMetadata] = useState<ParsedMetadata | null>(null);
 	const [uploading, setUploading] = useState(-1);
+	const [progress, setProgress] = useState<number | null>(null);
 	const [active, setActive] = useState(0);
 	const [error, setError] = useState('');
 	const finalMetaRef = useRef<{
@@ -66,8 +78,29 @@ export const AddSongModal = () => {
 							))}
 						</List>
 					)}
+					<TextInput
+						w='100%'
+						label='Filename template'
+						description='Fills in missing tags from the file names, like {track} - {artist} - {title}. Leave empty to use the server templates'
+						placeholder='{track} - {artist} - {title}'
+						disabled={!!ytUrl}
+						value={filenameTemplate}
+						onChange={(e) => setFilenameTemplate(e.target.value)}
+					/>
 					<Text>OR</Text>
-					<TextInput w='100%' label='YouTube URL' disabled={files.length > 0} value={ytUrl} onChange={(e) => setYtUrl(e.target.value)} />
+					<TextInput w='100%' label='YouTube URL' description='Playlists and channels import every video that was not imported yet' disabled={files.length > 0} value={ytUrl} onChange={(e) => setYtUrl(e.target.value)} />
+					<Checkbox
+						label='Tag songs with the playlist title'
+						disabled={!ytUrl}
+						checked={playlistAsTag}
+						onChange={(e) => setPlaylistAsTag(e.currentTarget.checked)}
+					/>
+					<Checkbox
+						label='Keep the original audio format'
+						disabled={!ytUrl}
+						checked={keepOriginalFormat}
+						onChange={(e) => setKeepOriginalFormat(e.currentTarget.checked)}
+					/>
 					{error && <Text c='red'>{error}</Text>}
 					<Button
 						disabled={!files.length && !ytUrl}
@@ -79,7 +112,11 @@ export const AddSongModal = () => {
 								setUploading,
 								setMetadata,
 								setError,
+								setProgress,
 								finalMetaRef,
+								playlistAsTag,
+								keepOriginalFormat,
+								filenameTemplate.trim() || undefined,
 							)
 								.then((done) => {
 									if (!done) {
@@ -113,6 +150,9 @@ export const AddSongModal = () => {
 						zIndex={1000}
 						overlayProps={{ radius: 'sm' }}
 					/>
+					{!metadata && progress !== null && (
+						<Progress value={progress} animated style={{ zIndex: 1001 }} />
+					)}
 					<TextInput
 						label='Title'
 						value={metadata?.title ?? ''}
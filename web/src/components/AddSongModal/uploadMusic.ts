@@ -2,37 +2,26 @@ import { FileWithPath } from '@mantine/dropzone';
 import { Dispatch, RefObject, SetStateAction } from 'react';
 import WebsocketAsPromised from 'websocket-as-promised';
 import { HOST } from '../../api';
+import { ClientMessage } from '../../types/ClientMessage';
+import { FinalMetadata } from '../../types/FinalMetadata';
+import { ServerMessage } from '../../types/ServerMessage';
+import { SongMetadata } from '../../types/SongMetadata';
+import { UploadedInitSongInfo } from '../../types/UploadedInitSongInfo';
+import { YtInitSongInfo } from '../../types/YtInitSongInfo';
 
 const scheme = location.protocol === 'http:' ? 'ws://' : 'wss://';
 
-export type ParsedMetadata = {
-	title: string | null;
-	album: string | null;
-	artists: string[];
-};
-
-export type FinalMetadata = {
-	title: string;
-	album: string;
-	artists: string[];
-};
+// Must match the server's PROTOCOL_VERSION, it closes the WS if it speaks another one
+const PROTOCOL_VERSION = 1;
 
-type YtInitSongInfo = {
-	url: string;
-};
+export type ParsedMetadata = SongMetadata;
+export type { FinalMetadata };
 
-type UploadedInitSongInfo = {
-	name: string;
-	size: number;
-	type: string;
-};
+// How many times a failed download or upload is retried before it's skipped
+const MAX_RETRIES = 2;
 
-type AddSongRes = {
-	createdAlbum: boolean | null;
-	addedAlbum: boolean | null;
-	createdArtists: boolean | null;
-	addedArtists: boolean | null;
-};
+// Must be at most the server's max chunk size
+const CHUNK_SIZE = 4 * 1024 * 1024;
 
 const isStringArray = (arr: any[]): arr is string[] => {
 	return typeof arr[0] === 'string';
@@ -43,10 +32,14 @@ const cleanYouTubeUrl = (urlString: string): string => {
 		const url = new URL(urlString);
 		const videoId = url.searchParams.get('v');
 
+		const listId = url.searchParams.get('list');
+
 		url.search = ''; // Clear all query parameters
 
 		if (videoId) {
 			url.searchParams.set('v', videoId); // Re-add only the 'v' parameter
+		} else if (listId) {
+			url.searchParams.set('list', listId); // Playlist url, import all of it
 		}
 
 		return url.toString();
@@ -61,67 +54,145 @@ export const handleUpload = async (
 	setUploading: Dispatch<SetStateAction<number>>,
 	setMetadata: Dispatch<SetStateAction<ParsedMetadata | null>>,
 	setError: Dispatch<SetStateAction<string>>,
+	setProgress: Dispatch<SetStateAction<number | null>>,
 	finalMetaRef: RefObject<{
 		resolve?: (meta: FinalMetadata) => void;
 		promise: Promise<FinalMetadata>;
 	}>,
+	playlistAsTag = false,
+	keepOriginalFormat = false,
+	filenameTemplate?: string,
 ): Promise<boolean> => {
 	if (files.length === 0) return false;
 
-	// Open WS
+	// Open WS and make sure we speak the same protocol
 	const ws = new WebsocketAsPromised(`${scheme}${HOST}/api/add-songs`, {});
 	await ws.open();
+	send(ws, { hello: { version: PROTOCOL_VERSION } });
+	const hello = await receive(ws);
+	if (!('hello' in hello)) {
+		const error = 'error' in hello ? hello.error.message : 'Unexpected message from server';
+		setError(error);
+		throw error;
+	}
 
 	if (isStringArray(files)) {
 		// Send the URL for yt-dlp to get
-		const infos = files.map((url) => ({ yt: { url: cleanYouTubeUrl(url) } satisfies YtInitSongInfo })).filter((info) => info.yt.url);
+		const infos = files.map((url) => ({
+			yt: {
+				url: cleanYouTubeUrl(url),
+				playlistAsTag,
+				options: keepOriginalFormat ? { audioFormat: 'best' } : undefined,
+			} satisfies YtInitSongInfo,
+		})).filter((info) => info.yt.url);
 
 		if (infos.length === 0) return false; // invalid url
 
-		ws.send(
-			JSON.stringify(infos)
-		);
+		send(ws, { songs: infos });
 	} else {
 		// Tell the server some info about the songs we'll upload
-		ws.send(
-			JSON.stringify(files.map(({ name, size, type }) => ({ uploaded: { name, size, type } satisfies UploadedInitSongInfo }))),
-		);
-
-
+		send(ws, {
+			songs: files.map((file) => ({
+				uploaded: {
+					name: file.name,
+					size: file.size,
+					type: file.type,
+					uploadId: localStorage.getItem(uploadKey(file)) ?? undefined,
+					filenameTemplate,
+				} satisfies UploadedInitSongInfo,
+			})),
+		});
+	}
+	// Playlists and channels are expanded by the server, so it tells us how many songs there are
+	const batch = await receive(ws);
+	if (!('batch' in batch)) {
+		const error = 'error' in batch ? batch.error.message : 'Expected the batch';
+		setError(error);
+		throw error;
+	}
+	if (batch.batch.skipped.length) {
+		console.log('skipped already imported videos', batch.batch.skipped);
 	}
+
 	// Upload each song
-	for (let i = 0; i < files.length; i++) {
+	for (let i = 0, retries = 0; i < batch.batch.songs; i++) {
 		setUploading(i);
-		if (!isStringArray(files)) {
-			ws.send(await files[i].arrayBuffer());
+		let meta = !isStringArray(files) ? await sendFile(ws, files[i]) : await receive(ws);
+		// yt-dlp downloads report progress until they're done
+		while ('progress' in meta) {
+			setProgress(meta.progress);
+			meta = await receive(ws);
 		}
-		const meta: ParsedMetadata | { error: string } = JSON.parse(
-			await waitForResponse(ws),
-		);
+		setProgress(null);
 		if ('error' in meta) {
-			setError(meta.error);
-			throw meta.error;
+			setError(meta.error.message);
+			throw meta.error.message;
+		}
+		// The rest of the batch goes on, retry the item a few times if it might work the next time
+		if ('itemFailed' in meta) {
+			const { name, error, retryable } = meta.itemFailed;
+			if (retryable && retries < MAX_RETRIES) {
+				retries++;
+				send(ws, { decision: 'retry' });
+				i--;
+				continue;
+			}
+			setError(`Skipped ${name}: ${error}`);
+			send(ws, { decision: 'skip' });
+			retries = 0;
+			continue;
+		}
+		retries = 0;
+		// Same file as a song we already have, add it again using that file or skip it
+		if ('duplicate' in meta) {
+			const link = confirm(
+				`This is the same file as "${meta.duplicate.title}", add it again anyway?`,
+			);
+			send(ws, { decision: link ? 'link' : 'skip' });
+			if (!link) continue;
+			meta = await receive(ws);
 		}
 
 		console.log(meta);
 
-		setMetadata(meta);
+		let parsed: ParsedMetadata;
+		if ('archive' in meta) {
+			// Only the album and artists can be edited, tracks keep the titles from their tags
+			const { album, artists, tracks } = meta.archive;
+			parsed = {
+				title: `${tracks.length} tracks`,
+				album,
+				artists,
+				decodeReport: tracks[0].decodeReport,
+				fromFilename: null,
+			};
+		} else if ('metadata' in meta) {
+			parsed = meta.metadata;
+		} else {
+			setError('Unexpected message from server');
+			throw meta;
+		}
+		const isArchive = 'archive' in meta;
+		setMetadata(parsed);
 		while (true) {
 			finalMetaRef.current.promise = new Promise((resolve) => {
 				finalMetaRef.current.resolve = resolve;
 			});
 			const finalMeta = await finalMetaRef.current.promise;
 			console.log('sent final meta', finalMeta);
-			ws.send(JSON.stringify(finalMeta));
-
-			const finalRes: AddSongRes | { error: string } = JSON.parse(
-				await waitForResponse(ws),
+			send(
+				ws,
+				isArchive
+					? { album: { album: finalMeta.album, artists: finalMeta.artists } }
+					: { metadata: finalMeta },
 			);
 
+			const finalRes = await receive(ws);
+
 			// Something wrong with out final meta
 			if ('error' in finalRes) {
-				setError(finalRes.error);
-				setMetadata(meta);
+				setError(finalRes.error.message);
+				setMetadata(parsed);
 				continue;
 			}
 
@@ -132,11 +203,47 @@ export const handleUpload = async (
 		}
 	}
 
+	const summary = await receive(ws);
+	if ('summary' in summary) {
+		console.log('upload summary', summary.summary);
+	}
 
 	await ws.close();
 	return true;
 };
 
+// Lets us resume the upload of the same file if the connection drops
+const uploadKey = (file: File) => `upload:${file.name}:${file.size}:${file.lastModified}`;
+
+/**
+ * Sends the file in chunks, starting from wherever the server says it has staged up to.
+ * Returns the server's next message once it has all of it, or whatever it sent instead.
+ */
+const sendFile = async (ws: WebsocketAsPromised, file: File): Promise<ServerMessage> => {
+	while (true) {
+		const status = await receive(ws);
+		if (!('upload' in status)) return status;
+
+		const { uploadId, offset, size } = status.upload;
+		if (offset >= size) {
+			localStorage.removeItem(uploadKey(file));
+			return receive(ws);
+		}
+		localStorage.setItem(uploadKey(file), uploadId);
+
+		const chunk = await file.slice(offset, offset + CHUNK_SIZE).arrayBuffer();
+		const message = new Uint8Array(8 + chunk.byteLength);
+		new DataView(message.buffer).setBigUint64(0, BigInt(offset));
+		message.set(new Uint8Array(chunk), 8);
+		ws.send(message);
+	}
+};
+
+const send = (ws: WebsocketAsPromised, message: ClientMessage) => ws.send(JSON.stringify(message));
+
+const receive = async (ws: WebsocketAsPromised): Promise<ServerMessage> =>
+	JSON.parse(await waitForResponse(ws));
+
 const waitForResponse = (ws: WebsocketAsPromised): Promise<any> =>
 	Promise.any([
 		new Promise((resolve) =>
@@ -151,7 +258,7 @@ const waitForResponse = (ws: WebsocketAsPromised): Promise<any> =>
 
 		if (typeof messageOrCloseEvent === 'object') {
 			// Closed D:
-			return JSON.stringify({ error: 'WS closed unexpectedly' });
+			return JSON.stringify({ error: { message: 'WS closed unexpectedly' } });
 		}
 
 		return messageOrCloseEvent;
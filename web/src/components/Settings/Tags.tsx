@@ -40,7 +40,16 @@ export const Tags = () => {
 
 	return (
 		<Stack align='center'>
-			<Button onClick={() => fetch(apiUrl('/albums/populate-covers'), { credentials: 'include' })}>
+			<Button
+				onClick={() =>
+					fetch(apiUrl('/jobs'), {
+						method: 'POST',
+						credentials: 'include',
+						headers: { 'Content-Type': 'application/json' },
+						body: JSON.stringify({ kind: 'populateAlbumCovers' }),
+					})
+				}
+			>
 				Populate Covers
 			</Button>
 			<Stack
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AddSongStep } from "./AddSongStep";
+
+/**
+ * Why adding a song failed. Nothing about it is left in the db, and whatever was
+ * written to the storage backend is removed again.
+ */
+export type AddSongFailure = { step: AddSongStep, error: string, 
+/**
+ * Objects in the storage backend that couldn't be removed
+ */
+leftBehind: Array<string>, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TrackMetadata } from "./TrackMetadata";
+
+export type ArchiveInfo = { 
+/**
+ * First album and artists found in the tracks' tags
+ */
+album: string | null, artists: Array<string>, 
+/**
+ * Whether there was a cover.jpg or folder.jpg in the archive
+ */
+hasCover: boolean, tracks: Array<TrackMetadata>, };
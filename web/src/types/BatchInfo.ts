@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type BatchInfo = { 
+/**
+ * How many songs the client should expect to go through
+ */
+songs: number, 
+/**
+ * Urls of videos that were already imported
+ */
+skipped: Array<string>, };
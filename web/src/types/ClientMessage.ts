@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Decision } from "./Decision";
+import type { FinalAlbumMetadata } from "./FinalAlbumMetadata";
+import type { FinalMetadata } from "./FinalMetadata";
+import type { Hello } from "./Hello";
+import type { InitSongInfo } from "./InitSongInfo";
+import type { ReplaceAudio } from "./ReplaceAudio";
+
+/**
+ * Everything the client sends as text
+ */
+export type ClientMessage = { "hello": Hello } | { "songs": Array<InitSongInfo> } | { "metadata": FinalMetadata } | { "album": FinalAlbumMetadata } | { "decision": Decision } | { "replace": ReplaceAudio };
@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of decoding every packet of a song
+ */
+export type DecodeReport = { 
+/**
+ * Seconds of audio that actually decoded
+ */
+duration: number, 
+/**
+ * Seconds of audio the container claims to have
+ */
+declaredDuration: number | null, packets: number, 
+/**
+ * Packets that failed to decode
+ */
+lostPackets: number, errors: Array<string>, 
+/**
+ * Error that stopped decoding early, if any
+ */
+fatalError: string | null, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DuplicateSong } from "./DuplicateSong";
+
+/**
+ * Songs that sound the same, found by their fingerprints
+ */
+export type DuplicateGroup = { 
+/**
+ * Lowest similarity between two songs that put them in this group
+ */
+similarity: number, songs: Array<DuplicateSong>, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DuplicateInfo = { 
+/**
+ * The song that has the same file
+ */
+songId: number, title: string, contentHash: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DuplicateSong = { songId: number, title: string, 
+/**
+ * Seconds
+ */
+duration: number, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AddSongFailure } from "./AddSongFailure";
+
+export type ErrorInfo = { message: string, 
+/**
+ * What went wrong when a song couldn't be added
+ */
+failure?: AddSongFailure, };
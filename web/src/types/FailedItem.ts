@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type FailedItem = { 
+/**
+ * Null for playlists and channels that couldn't be expanded
+ */
+index: number | null, name: string, error: string, };
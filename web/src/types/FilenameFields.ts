@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What a template found in a file's name
+ */
+export type FilenameFields = { 
+/**
+ * The template that matched
+ */
+template: string, track: number | null, title: string | null, album: string | null, artist: string | null, };
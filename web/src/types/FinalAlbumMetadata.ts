@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FinalTrackMetadata } from "./FinalTrackMetadata";
+
+/**
+ * Final metadata for every track of an archive at once
+ */
+export type FinalAlbumMetadata = { album: string | null, 
+/**
+ * For tracks that don't have their own
+ */
+artists?: Array<string>, storageBackend?: string, 
+/**
+ * In the same order as [`ArchiveInfo::tracks`], anything left out is taken from the tags
+ */
+tracks?: Array<FinalTrackMetadata>, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type FinalMetadata = { title: string, album: string | null, artists: Array<string>, storageBackend?: string, 
+/**
+ * Only used for the song's path, taken from the file if left out
+ */
+albumArtist?: string, track?: number, disc?: number, };
@@ -1,3 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type FsConfig = { root: string, };
+export type FsConfig = { root: string, 
+/**
+ * Where new songs are written, see [`PathTemplate`]
+ */
+path_template?: string, };
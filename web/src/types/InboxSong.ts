@@ -0,0 +1,34 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FilenameFields } from "./FilenameFields";
+import type { MetadataDiff } from "./MetadataDiff";
+import type { PendingReview } from "./PendingReview";
+import type { SongProvenance } from "./SongProvenance";
+
+/**
+ * A song waiting for review, with what it was imported with and what else we could use
+ */
+export type InboxSong = { 
+/**
+ * Current album and artists, which change if the song is edited while it's pending
+ */
+album: string | null, artists: Array<string>, 
+/**
+ * Parsed from the file, unless the import sent its own metadata
+ */
+imported: PendingReview, provenance: SongProvenance | null, 
+/**
+ * What a filename template finds in the name the song was imported with
+ */
+fromFilename: FilenameFields | null, 
+/**
+ * What a re-scan found in the file, if it differs
+ */
+rescan: MetadataDiff | null, id: number, title: string, 
+/**
+ * Set when an admin edits the metadata by hand, so re-scans won't overwrite it
+ */
+metadataEdited: boolean, 
+/**
+ * Set when a non-interactive import added it, the song isn't listed until it's approved
+ */
+pendingReview: boolean, createdAt: string, updatedAt: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { UploadedInitSongInfo } from "./UploadedInitSongInfo";
+import type { UrlInitSongInfo } from "./UrlInitSongInfo";
+import type { YtInitSongInfo } from "./YtInitSongInfo";
+
+export type InitSongInfo = { "yt": YtInitSongInfo } | { "uploaded": UploadedInitSongInfo } | { "url": UrlInitSongInfo };
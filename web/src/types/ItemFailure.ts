@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ItemStage } from "./ItemStage";
+
+/**
+ * Sent when an item of the batch couldn't be imported, the client answers with a
+ * [`ClientMessage::Decision`] and the session goes on with the next item unless it retries
+ */
+export type ItemFailure = { 
+/**
+ * Position of the item in the batch
+ */
+index: number, name: string, stage: ItemStage, error: string, 
+/**
+ * Whether it's worth trying again, like after a failed download. Corrupt files aren't.
+ */
+retryable: boolean, };
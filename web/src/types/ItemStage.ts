@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where importing an item failed
+ */
+export type ItemStage = "import" | "probe" | "corrupt" | "album";
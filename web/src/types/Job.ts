@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JobStatus } from "./JobStatus";
+
+/**
+ * A background task and its progress
+ */
+export type Job = { id: number, kind: string, 
+/**
+ * JSON of the parameters the job was started with
+ */
+params: string, status: JobStatus, progress: number, total: number | null, error: string | null, createdBy: string | null, startedAt: string | null, finishedAt: string | null, createdAt: string, updatedAt: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome for a single item a job worked on
+ */
+export type JobResult = { id: number, jobId: number, item: string, ok: boolean, detail: string | null, createdAt: string, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Difference between a song's metadata and what a re-scan parsed from its file
+ */
+export type MetadataDiff = { songId: number, jobId: number | null, currentTitle: string, currentAlbum: string | null, currentArtists: Array<string>, title: string, album: string | null, artists: Array<string>, 
+/**
+ * Song was edited by hand, so this diff won't be applied
+ */
+protected: boolean, createdAt: string, };
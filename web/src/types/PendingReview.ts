@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Metadata a song was imported with while it waits for an admin to approve it
+ */
+export type PendingReview = { songId: number, title: string, album: string | null, artists: Array<string>, albumArtist: string | null, track: number | null, disc: number | null, createdAt: string, };
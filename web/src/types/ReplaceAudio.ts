@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Which song gets a new file, sent over the add songs WS instead of final metadata
+ */
+export type ReplaceAudio = { songId: number, 
+/**
+ * Removes the song's other sources, and their objects if no other song uses them
+ */
+retireOld?: boolean, storageBackend?: string, };
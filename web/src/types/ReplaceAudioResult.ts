@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ReplaceAudioResult = { songId: number, sourceId: number, 
+/**
+ * Sources the song doesn't use anymore
+ */
+retired: number[], 
+/**
+ * Objects of retired sources that couldn't be removed
+ */
+leftBehind: Array<string>, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JobStatus } from "./JobStatus";
+
+/**
+ * A maintenance task that runs on an interval and how its last run went
+ */
+export type ScheduledTask = { name: string, everySecs: number, lastJobId: number | null, lastRunAt: string | null, lastStatus: JobStatus | null, lastError: string | null, nextRunAt: string | null, createdAt: string, updatedAt: string, };
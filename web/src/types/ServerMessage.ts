@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AddSongResult } from "./AddSongResult";
+import type { ArchiveInfo } from "./ArchiveInfo";
+import type { BatchInfo } from "./BatchInfo";
+import type { DuplicateInfo } from "./DuplicateInfo";
+import type { ErrorInfo } from "./ErrorInfo";
+import type { Hello } from "./Hello";
+import type { ItemFailure } from "./ItemFailure";
+import type { ReplaceAudioResult } from "./ReplaceAudioResult";
+import type { SongMetadata } from "./SongMetadata";
+import type { SummaryInfo } from "./SummaryInfo";
+import type { UploadStatus } from "./UploadStatus";
+
+/**
+ * Everything the server sends
+ */
+export type ServerMessage = { "hello": Hello } | { "batch": BatchInfo } | { "upload": UploadStatus } | { "progress": number | null } | { "duplicate": DuplicateInfo } | { "metadata": SongMetadata } | { "archive": ArchiveInfo } | { "added": AddSongResult } | { "replaced": ReplaceAudioResult } | { "songs": Array<AddSongResult> } | { "itemFailed": ItemFailure } | { "summary": SummaryInfo } | { "error": ErrorInfo };
@@ -1,3 +1,11 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type Song = { id: number, title: string, createdAt: string, updatedAt: string, };
+export type Song = { id: number, title: string, 
+/**
+ * Set when an admin edits the metadata by hand, so re-scans won't overwrite it
+ */
+metadataEdited: boolean, 
+/**
+ * Set when a non-interactive import added it, the song isn't listed until it's approved
+ */
+pendingReview: boolean, createdAt: string, updatedAt: string, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DecodeReport } from "./DecodeReport";
+import type { FilenameFields } from "./FilenameFields";
+
+export type SongMetadata = { title: string | null, album: string | null, artists: Array<string>, decodeReport: DecodeReport, 
+/**
+ * What a filename template found, if the tags were missing something
+ */
+fromFilename: FilenameFields | null, };
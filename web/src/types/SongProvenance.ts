@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportMethod } from "./ImportMethod";
+
+/**
+ * Where a song came from and who added it
+ */
+export type SongProvenance = { songId: number, uploadedBy: string | null, method: ImportMethod, 
+/**
+ * Original filename, or the url it was downloaded from
+ */
+originalName: string | null, videoId: string | null, channel: string | null, channelUrl: string | null, 
+/**
+ * As yt-dlp gives it, i.e. "20240131"
+ */
+uploadDate: string | null, webpageUrl: string | null, 
+/**
+ * Playlist or channel the song was imported from
+ */
+playlistUrl: string | null, importedAt: string, };
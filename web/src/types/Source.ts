@@ -3,4 +3,12 @@
 /**
  * Some kind of binary data in the storage backend
  */
-export type Source = { id: number, path: string, mimeType: string, storageBackendName: string, createdAt: string, updatedAt: string, };
+export type Source = { id: number, path: string, mimeType: string, storageBackendName: string, 
+/**
+ * SHA-256 of the data, used to find duplicates. Null until it's hashed.
+ */
+contentHash: string | null, 
+/**
+ * Added by a library scan where it already was, so we don't move or delete its object
+ */
+inPlace: boolean, createdAt: string, updatedAt: string, };
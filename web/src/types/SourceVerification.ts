@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of fully decoding a source, used to flag broken files
+ */
+export type SourceVerification = { sourceId: number, ok: boolean, 
+/**
+ * Seconds of audio that actually decoded
+ */
+duration: number, 
+/**
+ * Seconds of audio the container claims to have
+ */
+declaredDuration: number | null, packets: number, lostPackets: number, error: string | null, createdAt: string, updatedAt: string, };
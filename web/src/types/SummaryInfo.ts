@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FailedItem } from "./FailedItem";
+import type { SkippedItem } from "./SkippedItem";
+import type { SucceededItem } from "./SucceededItem";
+
+export type SummaryInfo = { succeeded: Array<SucceededItem>, 
+/**
+ * Items the client skipped, and videos that were already imported without an index
+ */
+skipped: Array<SkippedItem>, failed: Array<FailedItem>, };
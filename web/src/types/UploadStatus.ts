@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type UploadStatus = { 
+/**
+ * Send it again to resume the upload if the connection drops
+ */
+uploadId: string, offset: number, size: number, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type UploadedInitSongInfo = { name: string, size: number, type: string, 
+/**
+ * Set when resuming an upload that was interrupted
+ */
+uploadId?: string, 
+/**
+ * Used instead of the configured templates for filling in missing tags
+ */
+filenameTemplate?: string, };
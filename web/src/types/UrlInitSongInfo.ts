@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A file downloaded straight from a http(s) url, with metadata for whatever its tags leave out
+ */
+export type UrlInitSongInfo = { url: string, title?: string, album?: string, artists?: Array<string>, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Per-import changes to [`YtDlpOptions`]
+ */
+export type YtDlpOverrides = { audioFormat?: string, audioQuality?: string, embedMetadata?: boolean, embedThumbnail?: boolean, };
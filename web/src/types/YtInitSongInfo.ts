@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { YtDlpOverrides } from "./YtDlpOverrides";
+
+export type YtInitSongInfo = { url: string, 
+/**
+ * Tag every song from a playlist or channel with its title
+ */
+playlistAsTag?: boolean, 
+/**
+ * Changes to the configured yt-dlp options for this import
+ */
+options?: YtDlpOverrides, };